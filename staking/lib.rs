@@ -3,12 +3,17 @@
 #[ink::contract]
 mod staking {
     use ink::prelude::vec::Vec;
-    use ink::storage::Mapping;
-    use ink::storage::traits::{Storable, StorageLayout};
+    use ink::storage::{Lazy, Mapping};
+    use ink::storage::traits::StorageLayout;
+    #[cfg(not(test))]
     use openbrush::contracts::traits::psp22::PSP22Ref;
+    #[cfg(all(feature = "booster", not(test)))]
+    use openbrush::contracts::traits::psp34::PSP34Ref;
 
     #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, StorageLayout)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    /// `active_until` is stored, not derived: `force_unlock` and `stake_days` locks move it
+    /// off `started_at + period` months. The layout is what live deployments hold.
     pub struct StakeInfo {
         pub amount: u128,
         pub started_at: u64,
@@ -16,481 +21,5471 @@ mod staking {
         pub active_until: u64,
     }
 
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum StakingError {
+        NoStake,
+        ZeroAmount,
+        AmountExceedsStake,
+        BelowMinStake,
+        CapExceeded,
+        Overflow,
+        RewardOverflow,
+        PeriodNotFound,
+        PeriodExists,
+        StillLocked,
+        StillActive,
+        InsufficientRewards,
+        TooEarly,
+        Cooldown,
+        GracePeriodNotOver,
+        TransferFailed,
+        NotOwner,
+        NotOperator,
+        Paused,
+        Reentrant,
+        InvalidBps,
+        InvalidFee,
+        InvalidConversionRate,
+        InvalidRewardPeriod,
+        RewardTokenMismatch,
+        RewardPoolNotEmpty,
+        SelfReferral,
+        TimestampInPast,
+        PeriodInUse,
+        InvalidSchedule,
+        StaleNonce,
+        MigrationClosed,
+        PeriodTooLong,
+        PeriodTooShort,
+        PeriodNotAllowed,
+        WithdrawNotRequested,
+        Unbonding,
+        PoolCapExceeded,
+        Winddown,
+        PermitFailed,
+        PermitUnsupported,
+        RateAboveMax,
+        NoPendingRate,
+        InvalidDecimals,
+        InvalidDays,
+    }
+
+    /// `ParamChanged::key` of each numeric owner setting.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Param {
+        /// `set_reward_rate`
+        RewardRate = 0,
+        /// `set_early_withdraw_fee`
+        EarlyWithdrawFee = 1,
+        /// `set_reward_conversion_rate`
+        ConversionRate = 2,
+        /// `set_min_stake`
+        MinStake = 3,
+        /// `set_max_total_staked`
+        MaxTotalStaked = 4,
+        /// `set_reward_period`
+        RewardPeriod = 5,
+        /// `set_claim_cooldown`
+        ClaimCooldown = 6,
+        /// `set_grace_secs`
+        GraceSecs = 7,
+        /// `set_referral_bonus`
+        ReferralBonus = 8,
+        /// `secs` of `set_unbonding`
+        UnbondingSecs = 9,
+        /// `threshold` of `set_unbonding`
+        LargeThreshold = 10,
+        /// `bps` of `set_booster`
+        BoosterBps = 11,
+        /// `set_max_period`
+        MaxPeriod = 12,
+        /// `set_max_rewards_balance`
+        MaxRewardsBalance = 13,
+        /// `set_min_period`
+        MinPeriod = 14,
+        /// `bps` of `set_period_multiplier`, 100 before the first change
+        PeriodMultiplier = 15,
+        /// `rate` of `set_period_rate`, 0 while the period follows the base rate
+        PeriodRate = 16,
+        /// Length of the `set_allowed_days` list
+        AllowedDays = 17,
+    }
+
+    /// Owner change to what positions earn. All of them wait out `rate_change_delay`
+    /// and must keep the effective rate under `max_reward_rate`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, StorageLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum RateChange {
+        /// `set_reward_rate`
+        RewardRate(u128),
+        /// `set_period_multiplier`
+        PeriodMultiplier(u32, u128),
+        /// `set_period_rate`
+        PeriodRate(u32, u128),
+        /// `set_rate_schedule`
+        RateSchedule(Vec<(u64, u128)>),
+        /// `set_booster`
+        Booster(Option<AccountId>, u128),
+    }
+
+    impl RateChange {
+        /// Whether both change the same setting, so the later one replaces the earlier.
+        fn replaces(&self, other: &RateChange) -> bool {
+            match (self, other) {
+                (RateChange::PeriodMultiplier(a, _), RateChange::PeriodMultiplier(b, _)) => a == b,
+                (RateChange::PeriodRate(a, _), RateChange::PeriodRate(b, _)) => a == b,
+                _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+            }
+        }
+    }
+
+    /// Entry of the per-account log kept in `history`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum StakeEvent {
+        Staked { timestamp: u64, amount: u128 },
+        Claimed { timestamp: u64, amount: u128 },
+        Withdrew { timestamp: u64, amount: u128 },
+        Extended { timestamp: u64, amount: u128 },
+    }
+
+    /// `(owed, periods)` a `ClaimSkipped` left unpaid, priced at the principal of the time.
+    pub type Owed = (u128, u32);
+
+    /// Entries kept per account in `history`, older ones are dropped.
+    const HISTORY_LEN: usize = 50;
+
+    /// Most accounts a single batch query looks at.
+    const MAX_BATCH: usize = 100;
+
+    /// Widest gap between `reward_token_decimals` and `native_decimals`, 10^38 is the
+    /// largest power of ten a u128 holds.
+    const MAX_DECIMALS_DIFF: u8 = 38;
+
+    /// `reward_rate` of a fresh deployment, `max_reward_rate` may not be below it.
+    const DEFAULT_REWARD_RATE: u128 = 5;
+
+    /// Calls into the token contracts. Unit tests run against `crate::mock::MockLedger`
+    /// because the off-chain environment cannot dispatch cross-contract calls.
+    pub trait TokenLedger {
+        /// PSP22 `transfer` of `amount` from this contract to `to`.
+        fn transfer(token: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError>;
+        /// PSP22 `transfer_from`, spending this contract's allowance of `from`.
+        fn transfer_from(token: AccountId, from: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError>;
+        fn balance_of(token: AccountId, owner: AccountId) -> Balance;
+        /// `PSP22Permit::permit` letting `spender` move `amount` of `owner`'s tokens.
+        fn permit(token: AccountId, owner: AccountId, spender: AccountId, amount: u128, deadline: u64, signature: Vec<u8>) -> Result<(), StakingError>;
+        /// Whether `account` holds a token of the PSP34 `collection`.
+        fn holds_booster(collection: AccountId, account: AccountId) -> bool;
+    }
+
+    /// Ledger of deployed builds, calls the token contracts directly.
+    #[cfg(not(test))]
+    pub struct ContractLedger;
+
+    #[cfg(not(test))]
+    type Tokens = ContractLedger;
+    #[cfg(test)]
+    type Tokens = crate::mock::MockLedger;
+
+    #[cfg(not(test))]
+    impl TokenLedger for ContractLedger {
+        fn transfer(token: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            PSP22Ref::transfer(&token, to, amount, Vec::new()).map_err(|_| StakingError::TransferFailed)
+        }
+
+        fn transfer_from(token: AccountId, from: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            PSP22Ref::transfer_from(&token, from, to, amount, Vec::new()).map_err(|_| StakingError::TransferFailed)
+        }
+
+        fn balance_of(token: AccountId, owner: AccountId) -> Balance {
+            PSP22Ref::balance_of(&token, owner)
+        }
+
+        #[cfg(feature = "permit")]
+        fn permit(token: AccountId, owner: AccountId, spender: AccountId, amount: u128, deadline: u64, signature: Vec<u8>) -> Result<(), StakingError> {
+            use ink::env::call::{build_call, ExecutionInput, Selector};
+            use openbrush::contracts::traits::psp22::PSP22Error;
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22Permit::permit")))
+                        .push_arg(owner)
+                        .push_arg(spender)
+                        .push_arg(amount)
+                        .push_arg(deadline)
+                        .push_arg(signature),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| StakingError::PermitFailed)?
+                .map_err(|_| StakingError::PermitFailed)?
+                .map_err(|_| StakingError::PermitFailed)
+        }
+
+        #[cfg(not(feature = "permit"))]
+        fn permit(_token: AccountId, _owner: AccountId, _spender: AccountId, _amount: u128, _deadline: u64, _signature: Vec<u8>) -> Result<(), StakingError> {
+            Err(StakingError::PermitUnsupported)
+        }
+
+        #[cfg(feature = "booster")]
+        fn holds_booster(collection: AccountId, account: AccountId) -> bool {
+            PSP34Ref::balance_of(&collection, account) > 0
+        }
+
+        #[cfg(not(feature = "booster"))]
+        fn holds_booster(_collection: AccountId, _account: AccountId) -> bool {
+            false
+        }
+    }
+
+    /// Snapshot of position 0 returned by `all_stake_info`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct StakeView {
+        pub amount: u128,
+        pub started_at: u64,
+        pub period: u32,
+        pub active_until: u64,
+        pub pending_rewards: u128,
+        pub next_reward_at: u64,
+    }
+
     
     #[ink(storage)]
     pub struct Staking {
-        pub stakes: Mapping<AccountId, StakeInfo>,
+        pub stakes: Mapping<(AccountId, u32), StakeInfo>,
+        pub position_count: Mapping<AccountId, u32>,
         pub level_periods: Mapping<u32, Vec<u32>>,
-        pub last_reward_claims: Mapping<AccountId, u64>,
+        pub levels: Mapping<AccountId, u32>,
+        pub auto_compound: Mapping<AccountId, bool>,
+        pub withdraw_ready_at: Mapping<(AccountId, u32), u64>,
+        pub last_reward_claims: Mapping<(AccountId, u32), u64>,
+        pub owed_rewards: Mapping<(AccountId, u32), Owed>,
         pub operators: Mapping<AccountId, bool>,
+        pub referrers: Mapping<AccountId, AccountId>,
+        pub referral_earnings: Mapping<AccountId, u128>,
+        pub action_nonce: Mapping<AccountId, u64>,
+        pub history: Mapping<AccountId, Vec<StakeEvent>>,
+        pub period_multipliers: Mapping<u32, u128>,
+        pub period_reward_rate: Mapping<u32, u128>,
+        pub staked_by_period: Mapping<u32, u128>,
+        /// `(paused_at, resumed_at)` of each finished `pause_rewards`, oldest first.
+        pub reward_pauses: Mapping<u32, (u64, u64)>,
+        pub reward_pause_count: u32,
+        /// Accounts with an open position by index, `staker_index` maps them back.
+        pub stakers: Mapping<u32, AccountId>,
+        pub staker_index: Mapping<AccountId, u32>,
         pub available_periods: Vec<u32>,
+        pub staking_token: AccountId,
         pub reward_token: AccountId,
         pub total_staked: u128,
+        pub staker_count: u32,
+        /// Positions of each account holding stake, moved on every zero/non-zero transition.
+        pub open_positions: Mapping<AccountId, u32>,
+        /// Reward budget in native units, see `update_rewards_pool`. It is what claims are charged in.
         pub rewards_balance: u128,
+        /// Reward tokens held for payouts, see `fund_rewards_psp22`. Claims need both to cover them.
+        pub reward_token_balance: u128,
+        pub total_rewards_distributed: u128,
         pub reward_rate: u128,
+        pub max_reward_rate: u128,
+        pub rate_change_delay: u64,
+        /// `(change, effective_at)` waiting for `apply_pending_rate`, at most one per setting.
+        pub pending_rate_changes: Lazy<Vec<(RateChange, u64)>>,
+        pub rate_schedule: Vec<(u64, u128)>,
+        pub allowed_days: Vec<u32>,
         pub early_withdraw_fee: u128,
+        pub fee_recipient: AccountId,
+        pub min_stake: u128,
+        pub max_total_staked: u128,
+        pub max_rewards_balance: u128,
+        pub max_period: u32,
+        pub min_period: u32,
+        pub booster_contract: Option<AccountId>,
+        pub booster_bps: u128,
+        pub reward_period_secs: u64,
+        pub grace_secs: u64,
+        pub unbonding_secs: u64,
+        pub large_threshold: u128,
+        pub claim_cooldown_secs: u64,
+        pub referral_bonus_bps: u128,
         pub reward_conversion_rate: u128,
+        pub reward_token_decimals: u8,
+        pub native_decimals: u8,
+        pub owner: AccountId,
+        pub paused: bool,
+        pub migration_open: bool,
+        pub winddown: bool,
+        pub rewards_paused: bool,
+        pub round_up_rewards: bool,
+        pub rewards_paused_at: u64,
+        pub locked: Lazy<bool>,
     }
 
     
     impl Staking {
         #[ink(constructor)]
-        pub fn new(reward_token: AccountId, reward_conversion_rate: u128) -> Self {
-            let mut available_periods = Vec::new();
-            available_periods.push(6);
-            available_periods.push(12);
+        /// `reward_conversion_rate` is reward tokens per pool unit in whole tokens; the
+        /// decimals scale it to the smallest units of each side. `max_reward_rate` caps every
+        /// later rate and `rate_change_delay` is how long a new `reward_rate` waits before
+        /// it applies. Panics on a zero conversion rate or decimals more than
+        /// `MAX_DECIMALS_DIFF` apart, see `try_new`.
+        pub fn new(
+            staking_token: AccountId,
+            reward_token: AccountId,
+            reward_conversion_rate: u128,
+            reward_token_decimals: u8,
+            native_decimals: u8,
+            max_reward_rate: u128,
+            rate_change_delay: u64,
+        ) -> Self {
+            assert!(reward_conversion_rate > 0, "zero reward conversion rate");
+            assert!(reward_token_decimals.abs_diff(native_decimals) <= MAX_DECIMALS_DIFF, "decimals too far apart");
+            assert!(max_reward_rate >= DEFAULT_REWARD_RATE, "reward rate above max");
+            Self::_init(staking_token, reward_token, reward_conversion_rate, reward_token_decimals, native_decimals, max_reward_rate, rate_change_delay)
+        }
+
+        /// Same as `new`, but rejects a zero conversion rate, which would turn every
+        /// reward transfer into zero tokens, and decimals no u128 can scale between.
+        #[ink(constructor)]
+        pub fn try_new(
+            staking_token: AccountId,
+            reward_token: AccountId,
+            reward_conversion_rate: u128,
+            reward_token_decimals: u8,
+            native_decimals: u8,
+            max_reward_rate: u128,
+            rate_change_delay: u64,
+        ) -> Result<Self, StakingError> {
+            if reward_conversion_rate == 0 {
+                return Err(StakingError::InvalidConversionRate);
+            }
+            if reward_token_decimals.abs_diff(native_decimals) > MAX_DECIMALS_DIFF {
+                return Err(StakingError::InvalidDecimals);
+            }
+            if max_reward_rate < DEFAULT_REWARD_RATE {
+                return Err(StakingError::RateAboveMax);
+            }
+            Ok(Self::_init(staking_token, reward_token, reward_conversion_rate, reward_token_decimals, native_decimals, max_reward_rate, rate_change_delay))
+        }
+
+        fn _init(
+            staking_token: AccountId,
+            reward_token: AccountId,
+            reward_conversion_rate: u128,
+            reward_token_decimals: u8,
+            native_decimals: u8,
+            max_reward_rate: u128,
+            rate_change_delay: u64,
+        ) -> Self {
+            let available_periods = ink::prelude::vec![6, 12];
 
             Self {
                 stakes: Mapping::new(),
+                position_count: Mapping::new(),
                 level_periods: Mapping::new(),
+                levels: Mapping::new(),
+                auto_compound: Mapping::new(),
+                withdraw_ready_at: Mapping::new(),
                 last_reward_claims: Mapping::new(),
+                owed_rewards: Mapping::new(),
                 operators: Mapping::new(),
+                referrers: Mapping::new(),
+                referral_earnings: Mapping::new(),
+                action_nonce: Mapping::new(),
+                history: Mapping::new(),
+                period_multipliers: Mapping::new(),
+                period_reward_rate: Mapping::new(),
+                staked_by_period: Mapping::new(),
+                reward_pauses: Mapping::new(),
+                reward_pause_count: 0,
+                stakers: Mapping::new(),
+                staker_index: Mapping::new(),
+                open_positions: Mapping::new(),
                 available_periods,
+                staking_token,
                 reward_token,
                 total_staked: 0,
+                staker_count: 0,
                 rewards_balance: 0,
-                reward_rate: 5,
+                reward_token_balance: 0,
+                total_rewards_distributed: 0,
+                reward_rate: DEFAULT_REWARD_RATE,
+                max_reward_rate,
+                rate_change_delay,
+                pending_rate_changes: Lazy::new(),
+                rate_schedule: Vec::new(),
+                allowed_days: Vec::new(),
                 early_withdraw_fee: 10,
+                fee_recipient: Self::env().caller(),
+                min_stake: 0,
+                max_total_staked: 0,
+                max_rewards_balance: 0,
+                max_period: 0,
+                min_period: 0,
+                booster_contract: None,
+                booster_bps: 10000,
+                reward_period_secs: 86400,
+                grace_secs: 30 * 86400,
+                unbonding_secs: 0,
+                large_threshold: 0,
+                claim_cooldown_secs: 0,
+                referral_bonus_bps: 0,
                 reward_conversion_rate,
+                reward_token_decimals,
+                native_decimals,
+                owner: Self::env().caller(),
+                paused: false,
+                migration_open: true,
+                winddown: false,
+                rewards_paused: false,
+                round_up_rewards: false,
+                rewards_paused_at: 0,
+                locked: Lazy::new(),
+            }
+        }
+
+        /// Lock period of position 0 in months, as passed to `stake`.
+        #[ink(message)]
+        pub fn get_staking_period(&self, account: AccountId) -> Result<u32, StakingError> {
+            self.stakes.get((account, 0))
+                .map(|stake_info| stake_info.period)
+                .ok_or(StakingError::NoStake)
+        }
+
+        #[ink(message)]
+        pub fn available_rewards(&self, account: AccountId) -> Result<u128, StakingError> {
+            let (_, reward) = self.reward_amount(account, 0)?;
+            Ok(reward)
+        }
+
+
+        /// `(reward_rate, early_withdraw_fee, reward_conversion_rate, available_periods)` in one read.
+        #[ink(message)]
+        pub fn config(&self) -> (u128, u128, u128, Vec<u32>) {
+            (self.reward_rate, self.early_withdraw_fee, self.reward_conversion_rate, self.available_periods.clone())
+        }
+
+        /// `(total_staked, rewards_balance, reward_rate, total_rewards_distributed)` in one read.
+        #[ink(message)]
+        pub fn stats(&self) -> (u128, u128, u128, u128) {
+            (self.total_staked, self.rewards_balance, self.reward_rate, self.total_rewards_distributed)
+        }
+
+        /// Whether position 0 is still inside its lock, i.e. `withdraw` would fail.
+        #[ink(message)]
+        pub fn is_locked(&self, account: AccountId) -> Result<bool, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            Ok(self._is_locked(&stake_info))
+        }
+
+        /// The last `HISTORY_LEN` stakes, claims, withdrawals and extends of `account`, oldest first.
+        #[ink(message)]
+        pub fn history_of(&self, account: AccountId) -> Vec<StakeEvent> {
+            self.history.get(account).unwrap_or_default()
+        }
+
+        /// Lock time left on position 0 in `block_timestamp` units (ms on chain), 0 once unlocked.
+        #[ink(message)]
+        pub fn seconds_until_unlock(&self, account: AccountId) -> Result<u64, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            if self.winddown {
+                return Ok(0);
             }
+            Ok(stake_info.active_until.saturating_sub(self.env().block_timestamp()))
         }
 
+        /// `(account, amount, started_at, period, active_until)` of position 0 for each of up to
+        /// `MAX_BATCH` accounts. Accounts without a stake are left out.
         #[ink(message)]
-        pub fn get_staking_period(&self, account: AccountId) -> Result<u32, String> {
-            self.stakes.get(&account)
-                .map(|stake_info| ((stake_info.active_until - stake_info.started_at) / 86400) as u32)
-                .ok_or_else(|| "Stake info not found".to_string())
+        pub fn stake_info_many(&self, accounts: Vec<AccountId>) -> Vec<(AccountId, u128, u64, u32, u64)> {
+            accounts.into_iter()
+                .take(MAX_BATCH)
+                .filter_map(|account| {
+                    let info = self.stakes.get((account, 0)).filter(|info| info.amount > 0)?;
+                    Some((account, info.amount, info.started_at, info.period, info.active_until))
+                })
+                .collect()
         }
 
+        /// Principal currently locked with `period`, across all positions.
         #[ink(message)]
-        pub fn available_rewards(&self, account: AccountId) -> Result<u128, String> {
-            let (_, reward) = self.reward_amount(account)?;
+        pub fn staked_in_period(&self, period: u32) -> u128 {
+            self.staked_by_period.get(period).unwrap_or(0)
+        }
+
+        /// Principal of position 0, like PSP22 `balance_of`.
+        #[ink(message)]
+        pub fn staked_of(&self, account: AccountId) -> u128 {
+            self.stakes.get((account, 0)).map_or(0, |info| info.amount)
+        }
+
+        /// Rewards position 0 would have pending at `future_ts` if nothing else changes.
+        #[ink(message)]
+        pub fn projected_reward_at(&self, account: AccountId, future_ts: u64) -> Result<u128, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            if future_ts < self._accrued_from(account, 0, &stake_info) {
+                return Err(StakingError::TimestampInPast);
+            }
+            let (_, reward) = self._reward_at(account, 0, future_ts)?;
             Ok(reward)
         }
 
+        /// One reward period's worth for position 0 at the current rate, regardless of
+        /// elapsed time. Zero once the lock has expired or while rewards are paused.
+        #[ink(message)]
+        pub fn daily_reward(&self, account: AccountId) -> Result<u128, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let now = self.env().block_timestamp();
+            if self.rewards_paused || now >= stake_info.active_until {
+                return Ok(0);
+            }
+            let multiplier = self.period_multipliers.get(stake_info.period).unwrap_or(100);
+            let reward = self._rate_sum(stake_info.period, now, 1)
+                .and_then(|rate| stake_info.amount.checked_mul(rate))
+                .and_then(|value| value.checked_mul(multiplier))
+                .ok_or(StakingError::RewardOverflow)?;
+            self._boosted(account, self._scale_reward(reward))
+        }
 
         #[ink(message)]
-        pub fn passed_reward_periods(&self, account: AccountId) -> Result<u32, String> {
-            let (passed_periods, _) = self.reward_amount(account)?;
+        pub fn passed_reward_periods(&self, account: AccountId) -> Result<u32, StakingError> {
+            let (passed_periods, _) = self.reward_amount(account, 0)?;
             Ok(passed_periods)
         }
 
+        /// Full reward periods still ahead before `active_until`, from now or the last claim.
+        #[ink(message)]
+        pub fn remaining_reward_periods(&self, account: AccountId) -> Result<u32, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let from = self.env().block_timestamp().max(self._accrued_from(account, 0, &stake_info));
+            Ok((stake_info.active_until.saturating_sub(from) / self.reward_period_secs) as u32)
+        }
+
         #[ink(message)]
-        pub fn all_stake_info(&self, account: AccountId) -> Result<(u128, u64, u32, u64, u128, u64), String> {
-            let stake_info = self.stakes.get(&account).ok_or_else(|| "Stake info not found".to_string())?;
-            let amount = stake_info.amount;
-            let started_at = stake_info.started_at;
-            let period = stake_info.period;
-            let active_until = stake_info.active_until;
-            let (rewards, next_reward_seconds) = if amount != 0 {
-                let (_, reward) = self.reward_amount(account)?;
+        pub fn all_stake_info(&self, account: AccountId) -> Result<StakeView, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let (pending_rewards, next_reward_at) = if stake_info.amount != 0 {
+                let (_, reward) = self.reward_amount(account, 0)?;
                 let next_reward_seconds = self.next_reward_date(account)?;
                 (reward, next_reward_seconds)
             } else {
                 (0, 0)
             };
 
-            Ok((amount, started_at, period, active_until, rewards, next_reward_seconds))
+            Ok(StakeView {
+                amount: stake_info.amount,
+                started_at: stake_info.started_at,
+                period: stake_info.period,
+                active_until: stake_info.active_until,
+                pending_rewards,
+                next_reward_at,
+            })
         }
 
+        /// Base annualized yield of `period` in basis points at the rate in effect now, using
+        /// the same math as `daily_reward`. Booster holders earn more, see `apy_for`.
         #[ink(message)]
-        pub fn next_reward_date(&self, account: AccountId) -> Result<u64, String> {
-            self._next_reward_date(account)
+        pub fn apy_for_period(&self, period: u32) -> Result<u128, StakingError> {
+            self._validate_period(period)?;
+            let periods_per_year = (365 * 86400 / self.reward_period_secs) as u128;
+            let multiplier = self.period_multipliers.get(period).unwrap_or(100);
+            self._rate_sum(period, self.env().block_timestamp(), 1)
+                .and_then(|rate| rate.checked_mul(multiplier))
+                .and_then(|value| value.checked_mul(periods_per_year))
+                .and_then(|value| value.checked_mul(10000))
+                .map(|value| value / 36000)
+                .ok_or(StakingError::RewardOverflow)
         }
 
-        #[ink(message, payable)]
-        pub fn stake(&mut self, period: u32) -> Result<(), String> {
-            let caller = self.env().caller();
-            let value = self.env().transferred_value();
-            assert!(value > 0, "amount should be > 0");
+        /// `apy_for_period` as `account` would earn it, booster bonus included.
+        #[ink(message)]
+        pub fn apy_for(&self, account: AccountId, period: u32) -> Result<u128, StakingError> {
+            self._boosted(account, self.apy_for_period(period)?)
+        }
 
-            let previous_amount = self.stakes.get(&caller).map(|info| info.amount).unwrap_or(0);
-            if previous_amount != 0 {
-                self._collect_rewards(caller, true)?;
-            }
-            self._stake(caller, period, value)?;
-            Ok(())
+        #[ink(message)]
+        pub fn total_staked(&self) -> u128 {
+            self.total_staked
+        }
+
+        /// Lifetime rewards paid out, in the same unit as `rewards_balance`.
+        #[ink(message)]
+        pub fn total_rewards_distributed(&self) -> u128 {
+            self.total_rewards_distributed
+        }
+
+        #[ink(message)]
+        pub fn next_reward_date(&self, account: AccountId) -> Result<u64, StakingError> {
+            self._next_reward_date(account, 0)
         }
 
+        /// Whether `claim` by `account` would pay something now: a period has passed, the
+        /// cooldown is over and the pool covers the reward with any referral bonus.
         #[ink(message)]
-        pub fn withdraw(&mut self) -> Result<(), String> {
-            let caller: ink::primitives::AccountId = self.env().caller();
-            if self.stakes.get(&caller).is_none() {
-                return Err("no stake".to_string());
+        pub fn can_claim(&self, account: AccountId) -> Result<bool, StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let accrued_from = self._accrued_from(account, 0, &stake_info);
+            if self.env().block_timestamp() < accrued_from.saturating_add(self.claim_cooldown_secs) {
+                return Ok(false);
             }
-            self._collect_rewards(caller, true)?;
-            let amount = self.stakes.get(&caller).ok_or_else(|| "Stake info not found".to_string())?.amount;
-            self._withdraw(caller, amount)?;
-            Ok(())
+            let (periods, reward) = self.reward_amount(account, 0)?;
+            Ok(periods > 0 && stake_info.amount > 0 && self._covers(reward + self._referral_bonus(account, reward)?))
         }
 
+        /// Stored reward checkpoint of position 0, 0 if none was written.
         #[ink(message)]
-        pub fn emergency_withdraw(&mut self) -> Result<(), String> {
-            let caller = self.env().caller();
-            if self.stakes.get(&caller).is_none() {
-                return Err("no stake".to_string());
-            }
-            let amount = self.stakes.get(&caller).unwrap().amount;
-            self._withdraw(caller, amount)?;
-            self.stakes.insert(caller, &StakeInfo {
-                amount: 0,
-                started_at: 0,
-                period: 0,
-                active_until: 0,
-            });
-            Ok(())
+        pub fn last_claim_at(&self, account: AccountId) -> u64 {
+            self.last_reward_claims.get((account, 0)).unwrap_or(0)
         }
 
+        /// `(pending_reward, passed_periods, next_reward_at)` of position 0 in one read.
         #[ink(message)]
-        pub fn extend(&mut self, period: u32) -> Result<(), String> {
+        pub fn reward_status(&self, account: AccountId) -> Result<(u128, u32, u64), StakingError> {
+            let (passed_periods, reward) = self.reward_amount(account, 0)?;
+            Ok((reward, passed_periods, self._next_reward_date(account, 0)?))
+        }
+
+        #[ink(message)]
+        pub fn position_count(&self, account: AccountId) -> u32 {
+            self.position_count.get(account).unwrap_or(0)
+        }
+
+        /// Whether any position of `account` holds principal. Exits leave a zeroed entry
+        /// behind, so a stored position alone doesn't count.
+        #[ink(message)]
+        pub fn has_stake(&self, account: AccountId) -> bool {
+            self.open_positions.get(account).unwrap_or(0) > 0
+        }
+
+        /// Accounts with at least one open position. `_set_stake_info` counts each account's
+        /// positions in `open_positions` and lists the account in `stakers` while that is
+        /// non-zero, so merges, partial exits and migrations can't skew it.
+        #[ink(message)]
+        pub fn staker_count(&self) -> u32 {
+            self.staker_count
+        }
+
+        /// Accounts with at least one open position, `len` of them from index `start`. An
+        /// exit moves the last account into the freed index, so pages may shift in between.
+        #[ink(message)]
+        pub fn stakers_paged(&self, start: u32, len: u32) -> Vec<AccountId> {
+            (start..start.saturating_add(len).min(self.staker_count))
+                .filter_map(|index| self.stakers.get(index))
+                .collect()
+        }
+
+        /// Pulls `amount` staking tokens from the caller, who must have approved this contract.
+        /// Returns the caller's resulting stake in position 0.
+        ///
+        /// A top-up moves the running lock end to the principal-weighted average of the time
+        /// left and `period`, never earlier. With `reset_lock`, or once the lock has expired,
+        /// the whole position is locked again for `period` from now instead.
+        #[ink(message)]
+        pub fn stake(&mut self, period: u32, amount: u128, reset_lock: bool) -> Result<u128, StakingError> {
             let caller = self.env().caller();
-            let stake_info = self.stakes.get(&caller).ok_or_else(|| "Stake info not found".to_string())?;
-            assert!(stake_info.amount > 0, "stake required");
-            assert!(stake_info.active_until < self.env().block_timestamp(), "still active");
-            self._collect_rewards(caller, true)?;
-            self._stake(caller, period, 0)?;
-            Ok(())
+            self._non_reentrant(|this| this._stake_from(caller, caller, period, amount, reset_lock))
         }
 
+        /// Like `stake`, but first redeems a PSP22 permit of the staking token for `amount`, so
+        /// no separate `approve` is needed. Needs the `permit` feature and a token with
+        /// `PSP22Permit::permit`.
         #[ink(message)]
-        pub fn claim(&mut self) -> Result<(), String> {
+        pub fn stake_with_permit(&mut self, period: u32, amount: u128, deadline: u64, signature: Vec<u8>) -> Result<u128, StakingError> {
             let caller = self.env().caller();
-            if self.stakes.get(&caller).is_none() {
-                return Err("no stake".to_string());
+            if deadline < self.env().block_timestamp() {
+                return Err(StakingError::TimestampInPast);
             }
-            self._collect_rewards(caller, false)?;
-            Ok(())
+            self._non_reentrant(|this| {
+                this._permit(caller, amount, deadline, signature)?;
+                this._stake_from(caller, caller, period, amount, false)
+            })
         }
 
-        #[ink(message, payable)]
-        pub fn update_rewards_pool(&mut self) -> Result<(), String> {
-            let value = self.env().transferred_value();
-            assert!(value > 0, "amount should be > 0");
-            self.rewards_balance += value;
-            self.env().emit_event(RewardPoolUpdated { amount: value });
-            Ok(())
+        /// Like `stake_new`, but the caller pays and `beneficiary` owns the new position. It is
+        /// never position 0, so nobody can top up or relock the beneficiary's own stake.
+        #[ink(message)]
+        pub fn stake_for(&mut self, beneficiary: AccountId, period: u32, amount: u128) -> Result<u32, StakingError> {
+            let caller = self.env().caller();
+            self._non_reentrant(|this| {
+                let position = this.position_count(beneficiary).max(1);
+                this._open_position(caller, beneficiary, position, period, amount)
+            })
         }
 
-        fn _validate_period(&self, period: u32) -> Result<(), String> {
-            if !self.available_periods.contains(&period) {
-                return Err("period not exist".to_string());
+        /// Like `stake`, and records `referrer` on the first one. The referrer then earns
+        /// `referral_bonus_bps` of every reward the caller collects, on top of it.
+        #[ink(message)]
+        pub fn stake_with_referrer(&mut self, period: u32, amount: u128, referrer: AccountId) -> Result<u128, StakingError> {
+            let caller = self.env().caller();
+            if referrer == caller {
+                return Err(StakingError::SelfReferral);
             }
-            Ok(())
+            if !self.referrers.contains(caller) {
+                self.referrers.insert(caller, &referrer);
+            }
+            self._non_reentrant(|this| this._stake_from(caller, caller, period, amount, false))
         }
 
-        fn reward_amount(&self, account: AccountId) -> Result<(u32, u128), String> {
-            let stake_info = self.stakes.get(&account).ok_or_else(|| "Stake info not found".to_string())?;
-            let time = if self.env().block_timestamp() > stake_info.active_until {
-                stake_info.active_until
-            } else {
-                self.env().block_timestamp()
-            };
-            let periods_passed = (time - self.last_reward_claims.get(&account).unwrap_or(0)) / 86400;
-            let reward = (stake_info.amount * self.reward_rate * periods_passed as u128 * 100) / 36000;
-            Ok((periods_passed as u32, reward))
+        #[ink(message)]
+        pub fn referral_earnings(&self, account: AccountId) -> u128 {
+            self.referral_earnings.get(account).unwrap_or(0)
         }
 
-        fn _next_reward_date(&self, account: AccountId) -> Result<u64, String> {
-            if let Some(last_claim) = self.last_reward_claims.get(&account) {
-                if let Some(stake_info) = self.stakes.get(&account) {
-                    if self.env().block_timestamp() > stake_info.active_until {
-                        Ok(stake_info.active_until)
-                    } else {
-                        let passed_periods = (self.env().block_timestamp() - stake_info.started_at) / 86400;
-                        Ok(((passed_periods + 1) * 86400) + stake_info.started_at)
-                    }
-                } else {
-                    Err("Stake info not found".to_string())
-                }
-            } else {
-                Err("Last reward claim not found".to_string())
+        /// Pays out the caller's referral bonus in reward tokens.
+        #[ink(message)]
+        pub fn claim_referral(&mut self) -> Result<u128, StakingError> {
+            let caller = self.env().caller();
+            let amount = self.referral_earnings(caller);
+            if amount == 0 {
+                return Err(StakingError::ZeroAmount);
             }
+            self._non_reentrant(|this| {
+                this.referral_earnings.remove(caller);
+                this.total_rewards_distributed += amount;
+                this._pay_reward(caller, this._reward_token_amount(amount)?)?;
+                this.env().emit_event(ReferralClaimed { account: caller, amount });
+                Ok(amount)
+            })
         }
 
-        fn _stake(&mut self, account: AccountId, periods: u32, amount: u128) -> Result<(), String> {
-            let new_amount = self.stakes.get(&account).map_or(amount, |info| info.amount + amount);
-            self._validate_period(periods)?;
-            let until = if amount == 0 {
-                self.env().block_timestamp() + (periods as u64 * 86400 * 30)
-            } else {
-                self.stakes.get(&account).map_or(0, |stake_info| stake_info.active_until)
-            };
-
-            self._set_stake_info(account, new_amount, periods, self.env().block_timestamp(), until)?;
-            self.total_staked += amount;
-            self.env().emit_event(Stake {
-                account,
-                staked_at: self.env().block_timestamp(),
-                period: periods,
-                sum: amount,
-                total_staked: new_amount,
-            });
+        #[ink(message)]
+        pub fn set_referral_bonus(&mut self, bps: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if bps > 10000 {
+                return Err(StakingError::InvalidBps);
+            }
+            self._param_changed(Param::ReferralBonus, self.referral_bonus_bps, bps);
+            self.referral_bonus_bps = bps;
             Ok(())
         }
 
-        fn _withdraw(&mut self, account: AccountId, amount: u128) -> Result<(), String> {
-            self._set_stake_info(account, 0, 0, 0, 0)?;
-            self.env().transfer(account, amount).map_err(|_| "Transfer failed".to_string())?;
-            self.env().emit_event(Withdraw {
-                account,
-                sum: amount,
-                is_early: false,
-            });
-            Ok(())
+        /// Opens a new position locked for `days` reward periods instead of months and returns
+        /// its id. `days` must be in `allowed_days`; the position is stored with period 0 and
+        /// never is position 0, which `stake` tops up with month locks.
+        #[ink(message)]
+        pub fn stake_days(&mut self, days: u32, amount: u128) -> Result<u32, StakingError> {
+            self._non_reentrant(|this| {
+                this._when_not_paused()?;
+                if !this.allowed_days.contains(&days) {
+                    return Err(StakingError::PeriodNotFound);
+                }
+                if amount == 0 {
+                    return Err(StakingError::ZeroAmount);
+                }
+                if amount < this.min_stake {
+                    return Err(StakingError::BelowMinStake);
+                }
+                let lock_secs = (days as u64).checked_mul(this.reward_period_secs).ok_or(StakingError::Overflow)?;
+                let caller = this.env().caller();
+                let position = this.position_count(caller).max(1);
+                this._transfer_stake_in(caller, amount)?;
+                this._stake_locked(caller, position, 0, lock_secs, amount, false)?;
+                Ok(position)
+            })
         }
 
-        fn _collect_rewards(&mut self, account: AccountId, not_direct: bool) -> Result<(), String> {
-            if let Some(stake_info) = self.stakes.get(&account) {
-                if stake_info.amount > 0 {
-                    let (periods, reward) = self.reward_amount(account)?;
-                    if not_direct && periods == 0 {
-                        return Ok(());
-                    }
-                    assert!(self.rewards_balance >= reward, "not enough rewards");
-                    assert!(periods > 0, "too early");
-                    let last_claim = self.last_reward_claims.get(&account).unwrap_or(0);
-                    self.last_reward_claims.insert(account, &(last_claim + ((86400 * periods) as u64)));
-                    self.rewards_balance -= reward;
-                    let reward_amount_in_reward_token = reward * self.reward_conversion_rate;
-                    self.env().emit_event(Claim {
-                        account,
-                        periods,
-                        amount: reward,
-                    });
-                    // Transfer the reward tokens to the account
-                    // Assuming the reward token follows the PSP22 standard
-                    // ink::env::call::build_call::<ink::env::DefaultEnvironment>()
-                    //     .call(self.reward_token)
-                    //     .gas_limit(5000)
-                    //     .transferred_value(0)
-                    //     .exec_input(
-                    //         ink::env::call::ExecutionInput::new(ink::env::call::Selector::new([0x23, 0xb8, 0x72, 0xdd])) // transfer selector
-                    //             .push_arg(account)
-                    //             .push_arg(reward_amount_in_reward_token),
-                    //     )
-                    //     .returns::<()>()
-                    //     .invoke();
-                    // Transfer the reward tokens to the account using the PSP22 interface
-                    PSP22Ref::transfer(&self.reward_token, account, reward_amount_in_reward_token, Vec::new()).map_err(|_| "Transfer failed".to_string())?;
-                }
+        /// Replaces the lock lengths `stake_days` accepts. Zero and repeated entries are
+        /// rejected with `InvalidDays`.
+        #[ink(message)]
+        pub fn set_allowed_days(&mut self, days: Vec<u32>) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if days.iter().enumerate().any(|(i, day)| *day == 0 || days[..i].contains(day)) {
+                return Err(StakingError::InvalidDays);
             }
+            self._param_changed(Param::AllowedDays, self.allowed_days.len() as u128, days.len() as u128);
+            self.allowed_days = days;
             Ok(())
         }
 
-        fn _set_stake_info(&mut self, account: AccountId, amount: u128, periods: u32, started_at: u64, until: u64) -> Result<(), String> {
-            self.stakes.insert(account, &StakeInfo {
-                amount,
-                started_at,
-                period: periods,
-                active_until: until,
+        #[ink(message)]
+        pub fn allowed_days(&self) -> Vec<u32> {
+            self.allowed_days.clone()
+        }
+
+        /// Opens an independent position instead of merging into position 0 and returns its id.
+        #[ink(message)]
+        pub fn stake_new(&mut self, period: u32, amount: u128) -> Result<u32, StakingError> {
+            self._non_reentrant(|this| {
+                let caller = this.env().caller();
+                let position = this.position_count(caller);
+                this._open_position(caller, caller, position, period, amount)
+            })
+        }
+
+        #[ink(message)]
+        pub fn request_withdraw(&mut self) -> Result<u64, StakingError> {
+            self.request_withdraw_position(0)
+        }
+
+        /// Starts the unbonding delay that positions above `large_threshold` must wait
+        /// out before any exit. Returns the time the position may leave.
+        #[ink(message)]
+        pub fn request_withdraw_position(&mut self, position: u32) -> Result<u64, StakingError> {
+            let caller = self.env().caller();
+            let stake_info = self.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+            if stake_info.amount == 0 {
+                return Err(StakingError::NoStake);
+            }
+            let ready_at = self.env().block_timestamp().saturating_add(self.unbonding_secs);
+            self.withdraw_ready_at.insert((caller, position), &ready_at);
+            self.env().emit_event(WithdrawRequested {
+                account: caller,
+                position,
+                ready_at,
             });
-            Ok(())
+            Ok(ready_at)
         }
-    }
 
-    #[ink(event)]
-    pub struct Stake {
-        #[ink(topic)]
-        account: AccountId,
-        staked_at: u64,
-        period: u32,
-        sum: u128,
-        total_staked: u128,
-    }
+        #[ink(message)]
+        pub fn withdraw(&mut self) -> Result<(), StakingError> {
+            self.withdraw_position(0)
+        }
 
-    #[ink(event)]
-    pub struct Withdraw {
-        #[ink(topic)]
-        account: AccountId,
-        sum: u128,
-        is_early: bool,
-    }
+        #[ink(message)]
+        pub fn withdraw_position(&mut self, position: u32) -> Result<(), StakingError> {
+            self._non_reentrant(|this| {
+                let caller: ink::primitives::AccountId = this.env().caller();
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                // Early exits go through `emergency_withdraw` instead.
+                if this._is_locked(&stake_info) {
+                    return Err(StakingError::StillLocked);
+                }
+                this._check_unbonded(caller, position, stake_info.amount)?;
+                this._collect_rewards(caller, position, true)?;
+                let amount = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?.amount;
+                this._withdraw(caller, position, amount, false)?;
+                Ok(())
+            })
+        }
 
-    #[ink(event)]
-    pub struct RewardPoolUpdated {
-        amount: u128,
-    }
+        /// Withdraws an unlocked position 0 without collecting, for when the pool can't pay
+        /// the pending rewards. Those rewards are forfeited.
+        #[ink(message)]
+        pub fn force_withdraw(&mut self) -> Result<(), StakingError> {
+            self._non_reentrant(|this| {
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, 0)).ok_or(StakingError::NoStake)?;
+                if this._is_locked(&stake_info) {
+                    return Err(StakingError::StillLocked);
+                }
+                this._check_unbonded(caller, 0, stake_info.amount)?;
+                let (_, forfeited) = this.reward_amount(caller, 0)?;
+                if forfeited > 0 {
+                    this.env().emit_event(RewardsForfeited { account: caller, amount: forfeited });
+                }
+                this._withdraw(caller, 0, stake_info.amount, false)
+            })
+        }
 
-    #[ink(event)]
-    pub struct Claim {
-        #[ink(topic)]
-        account: AccountId,
-        periods: u32,
-        amount: u128,
-    }
-}
+        #[ink(message)]
+        pub fn withdraw_partial(&mut self, amount: u128) -> Result<(), StakingError> {
+            self.withdraw_partial_position(0, amount)
+        }
 
-#[cfg(test)]
-mod tests {
-    use crate::staking::Staking;
-    use ink::env::{test, DefaultEnvironment};
-    use log::{info, debug};
-    use token::usdt_psp22::USDT;
-    use openbrush::contracts::traits::psp22::PSP22;
-    // Initialize the logger once for all tests in this module
-    fn init() {
-        let _ = env_logger::builder().is_test(true).try_init();
-    }
+        /// Withdraws part of an unlocked position, the rest keeps its lock settings.
+        #[ink(message)]
+        pub fn withdraw_partial_position(&mut self, position: u32, amount: u128) -> Result<(), StakingError> {
+            self._non_reentrant(|this| {
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                if amount == 0 {
+                    return Err(StakingError::ZeroAmount);
+                }
+                if amount > stake_info.amount {
+                    return Err(StakingError::AmountExceedsStake);
+                }
+                if this._is_locked(&stake_info) {
+                    return Err(StakingError::StillLocked);
+                }
+                this._check_unbonded(caller, position, stake_info.amount)?;
+                this._collect_rewards(caller, position, true)?;
+                // Auto-compound may have grown the principal while collecting.
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                this.withdraw_ready_at.remove((caller, position));
+                this._set_stake_info(caller, position, stake_info.amount - amount, stake_info.period, stake_info.started_at, stake_info.active_until)?;
+                this.total_staked = this.total_staked.saturating_sub(amount);
+                this._transfer_stake_out(caller, amount)?;
+                let timestamp = this.env().block_timestamp();
+                this._record(caller, StakeEvent::Withdrew { timestamp, amount });
+                this.env().emit_event(Withdraw {
+                    account: caller,
+                    sum: amount,
+                    is_early: false,
+                });
+                Ok(())
+            })
+        }
 
-    fn get_account_id_from_contract(contract_address: &dyn PSP22) -> ink::primitives::AccountId {
-        ink::env::account_id::<DefaultEnvironment>()
-    }
+        #[ink(message)]
+        pub fn emergency_withdraw(&mut self) -> Result<(), StakingError> {
+            self.emergency_withdraw_position(0)
+        }
 
-    fn create_sp22_token() -> (USDT, ink::primitives::AccountId) {
-        let reward_token = USDT::new(1_000_000);
-        let token_address = get_account_id_from_contract(&reward_token);
+        /// Withdraws a position whether or not it is locked, an early exit pays the fee.
+        #[ink(message)]
+        pub fn emergency_withdraw_position(&mut self, position: u32) -> Result<(), StakingError> {
+            self._non_reentrant(|this| {
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                let is_early = this._is_locked(&stake_info);
+                this._check_unbonded(caller, position, stake_info.amount)?;
+                this._withdraw(caller, position, stake_info.amount, is_early)
+            })
+        }
 
-        return (reward_token, token_address);
-        // Deploy the staking contract with the PSP22 token as the reward token
-    }
+        #[ink(message)]
+        pub fn compound(&mut self) -> Result<(), StakingError> {
+            self.compound_position(0)
+        }
 
-    #[ink::test]
+        /// Rolls the pending reward into the principal of the position instead of paying it
+        /// out. The reward has to be paid in the staked token for the contract to keep backing it,
+        /// and fit under `max_total_staked`.
+        #[ink(message)]
+        pub fn compound_position(&mut self, position: u32) -> Result<(), StakingError> {
+            self._when_not_paused()?;
+            if self.winddown {
+                return Err(StakingError::Winddown);
+            }
+            if self.reward_token != self.staking_token {
+                return Err(StakingError::RewardTokenMismatch);
+            }
+            let caller = self.env().caller();
+            let (periods, reward) = self.reward_amount(caller, position)?;
+            if periods == 0 {
+                return Err(StakingError::TooEarly);
+            }
+            if !self._covers(reward + self._referral_bonus(caller, reward)?) {
+                return Err(StakingError::InsufficientRewards);
+            }
+            self._compound_into(caller, position, periods, reward)
+        }
+
+        /// With it on, the rewards collected by `stake`, `withdraw` and the other exits
+        /// of the caller's positions are compounded instead of paid out.
+        #[ink(message)]
+        pub fn set_auto_compound(&mut self, on: bool) -> Result<(), StakingError> {
+            if on && self.reward_token != self.staking_token {
+                return Err(StakingError::RewardTokenMismatch);
+            }
+            let caller = self.env().caller();
+            self.auto_compound.insert(caller, &on);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn auto_compound(&self, account: AccountId) -> bool {
+            self.auto_compound.get(account).unwrap_or(false)
+        }
+
+        /// Claims position 0 into a new position locked for `period` instead of paying it
+        /// out. Like `compound`, needs the reward to be paid in the staked token. Returns the
+        /// new position id.
+        #[ink(message)]
+        pub fn claim_and_stake(&mut self, period: u32) -> Result<u32, StakingError> {
+            self._non_reentrant(|this| {
+                this._when_not_paused()?;
+                if this.reward_token != this.staking_token {
+                    return Err(StakingError::RewardTokenMismatch);
+                }
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, 0)).ok_or(StakingError::NoStake)?;
+                let (periods, reward) = this.reward_amount(caller, 0)?;
+                if periods == 0 {
+                    return Err(StakingError::TooEarly);
+                }
+                let amount = this._reward_token_amount(reward)?;
+                if amount == 0 {
+                    return Err(StakingError::ZeroAmount);
+                }
+                if amount < this.min_stake {
+                    return Err(StakingError::BelowMinStake);
+                }
+                if !this._covers(reward + this._referral_bonus(caller, reward)?) {
+                    return Err(StakingError::InsufficientRewards);
+                }
+                this._settle_rewards(caller, 0, &stake_info, periods, reward)?;
+                this.env().emit_event(Claim {
+                    account: caller,
+                    periods,
+                    amount: reward,
+                });
+                // The tokens stay in the contract, but now back principal instead of rewards.
+                this.reward_token_balance -= amount;
+                let position = this.position_count(caller);
+                this._stake(caller, position, period, amount, false)?;
+                Ok(position)
+            })
+        }
+
+        /// Imports a position from a previous deployment as `account`'s position 0, with
+        /// rewards accruing from `last_claim`. Only until `finalize_migration`.
+        #[ink(message)]
+        pub fn migrate_stake(&mut self, account: AccountId, info: StakeInfo, last_claim: u64) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if !self.migration_open {
+                return Err(StakingError::MigrationClosed);
+            }
+            self._validate_period(info.period)?;
+            let previous = self.stakes.get((account, 0)).map_or(0, |old| old.amount);
+            self.total_staked = self.total_staked
+                .saturating_sub(previous)
+                .checked_add(info.amount)
+                .ok_or(StakingError::Overflow)?;
+            self._set_stake_info(account, 0, info.amount, info.period, info.started_at, info.active_until)?;
+            self._checkpoint(account, 0, last_claim);
+            self._bump_nonce(account);
+            if self.position_count(account) == 0 {
+                self.position_count.insert(account, &1);
+            }
+            Ok(())
+        }
+
+        /// Closes `migrate_stake` for good.
+        #[ink(message)]
+        pub fn finalize_migration(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.migration_open = false;
+            Ok(())
+        }
+
+        /// Ends the lock of `account`'s position 0 now, so it can `withdraw` without the fee.
+        /// `expected_nonce` must equal `nonce_of(account)`.
+        #[ink(message)]
+        pub fn force_unlock(&mut self, account: AccountId, expected_nonce: u64) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._use_nonce(account, expected_nonce)?;
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let now = self.env().block_timestamp();
+            if now < stake_info.active_until {
+                self._set_stake_info(account, 0, stake_info.amount, stake_info.period, stake_info.started_at, now)?;
+            }
+            self.env().emit_event(ForceUnlocked {
+                account,
+                old_until: stake_info.active_until,
+            });
+            Ok(())
+        }
+
+        /// Releases the unclaimed rewards of a position expired for longer than `grace_secs`
+        /// without paying them. Returns the amount that no longer has to be covered.
+        #[ink(message)]
+        pub fn reclaim_expired(&mut self, account: AccountId) -> Result<u128, StakingError> {
+            self._only_operator()?;
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            if self.env().block_timestamp() <= stake_info.active_until.saturating_add(self.grace_secs) {
+                return Err(StakingError::GracePeriodNotOver);
+            }
+            self._bump_nonce(account);
+            let (_, amount) = self.reward_amount(account, 0)?;
+            self._checkpoint(account, 0, stake_info.active_until);
+            self.env().emit_event(RewardsReclaimed { account, amount });
+            Ok(amount)
+        }
+
+        #[ink(message)]
+        pub fn set_claim_cooldown(&mut self, secs: u64) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::ClaimCooldown, self.claim_cooldown_secs as u128, secs as u128);
+            self.claim_cooldown_secs = secs;
+            Ok(())
+        }
+
+        /// Positions above `threshold` need a matured `request_withdraw` to exit, 0 disables it.
+        #[ink(message)]
+        pub fn set_unbonding(&mut self, secs: u64, threshold: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::UnbondingSecs, self.unbonding_secs as u128, secs as u128);
+            self.unbonding_secs = secs;
+            self._param_changed(Param::LargeThreshold, self.large_threshold, threshold);
+            self.large_threshold = threshold;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_grace_secs(&mut self, secs: u64) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::GraceSecs, self.grace_secs as u128, secs as u128);
+            self.grace_secs = secs;
+            Ok(())
+        }
+
+        /// Takes `bps` basis points of `account`'s position 0 and sends them to `fee_recipient`.
+        /// Returns the slashed amount. `expected_nonce` must equal `nonce_of(account)`.
+        #[ink(message)]
+        pub fn slash(&mut self, account: AccountId, bps: u128, expected_nonce: u64) -> Result<u128, StakingError> {
+            self._only_operator()?;
+            if bps > 10000 {
+                return Err(StakingError::InvalidBps);
+            }
+            self._use_nonce(account, expected_nonce)?;
+            self._non_reentrant(|this| {
+                let stake_info = this.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+                let amount = stake_info.amount.checked_mul(bps).ok_or(StakingError::Overflow)? / 10000;
+                this._set_stake_info(account, 0, stake_info.amount - amount, stake_info.period, stake_info.started_at, stake_info.active_until)?;
+                this.total_staked = this.total_staked.saturating_sub(amount);
+                let recipient = this.fee_recipient;
+                this._transfer_stake_out(recipient, amount)?;
+                this.env().emit_event(Slashed { account, amount });
+                Ok(amount)
+            })
+        }
+
+        /// Operator and owner actions taken against `account` so far: `slash`, `force_unlock`,
+        /// `reclaim_expired`, `set_level`, `migrate_stake` and each `claim_for_many` payout.
+        /// `slash` and `force_unlock` only go through when passed the current value.
+        #[ink(message)]
+        pub fn nonce_of(&self, account: AccountId) -> u64 {
+            self.action_nonce.get(account).unwrap_or(0)
+        }
+
+        /// `(fee, net_amount)` that `emergency_withdraw` would charge and pay out right now.
+        #[ink(message)]
+        pub fn early_withdraw_preview(&self, account: AccountId) -> Result<(u128, u128), StakingError> {
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            let is_early = self._is_locked(&stake_info);
+            self._withdraw_split(stake_info.amount, is_early)
+        }
+
+        /// `active_until` that `extend(period)` by `account` would set now, with the same checks.
+        #[ink(message)]
+        pub fn extend_preview(&self, account: AccountId, period: u32) -> Result<u64, StakingError> {
+            self._when_not_paused()?;
+            if self.winddown {
+                return Err(StakingError::Winddown);
+            }
+            self._validate_period(period)?;
+            self._check_level(account, period)?;
+            let stake_info = self.stakes.get((account, 0)).ok_or(StakingError::NoStake)?;
+            if stake_info.amount == 0 {
+                return Err(StakingError::NoStake);
+            }
+            let now = self.env().block_timestamp();
+            if stake_info.active_until >= now {
+                return Err(StakingError::StillActive);
+            }
+            Ok(now + period as u64 * 86400 * 30)
+        }
+
+        #[ink(message)]
+        pub fn extend(&mut self, period: u32) -> Result<u64, StakingError> {
+            self.extend_position(0, period)
+        }
+
+        /// Relocks an expired position for `period` months. Returns the new `active_until`.
+        #[ink(message)]
+        pub fn extend_position(&mut self, position: u32, period: u32) -> Result<u64, StakingError> {
+            self._non_reentrant(|this| {
+                this._when_not_paused()?;
+                this._validate_period(period)?;
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                if stake_info.amount == 0 {
+                    return Err(StakingError::NoStake);
+                }
+                if stake_info.active_until >= this.env().block_timestamp() {
+                    return Err(StakingError::StillActive);
+                }
+                this._collect_rewards(caller, position, true)?;
+                this._stake(caller, position, period, 0, false)?;
+                let new_until = this.stakes.get((caller, position)).map_or(0, |info| info.active_until);
+                this.env().emit_event(Extended {
+                    account: caller,
+                    old_until: stake_info.active_until,
+                    new_until,
+                    period,
+                });
+                Ok(new_until)
+            })
+        }
+
+        #[ink(message)]
+        pub fn claim(&mut self) -> Result<(), StakingError> {
+            self.claim_position(0)
+        }
+
+        #[ink(message)]
+        pub fn claim_position(&mut self, position: u32) -> Result<(), StakingError> {
+            self._non_reentrant(|this| {
+                let caller = this.env().caller();
+                let stake_info = this.stakes.get((caller, position)).ok_or(StakingError::NoStake)?;
+                // Only direct claims wait; collects during stake and withdraw don't.
+                let accrued_from = this._accrued_from(caller, position, &stake_info);
+                if this.env().block_timestamp() < accrued_from.saturating_add(this.claim_cooldown_secs) {
+                    return Err(StakingError::Cooldown);
+                }
+                this._collect_rewards(caller, position, false)?;
+                Ok(())
+            })
+        }
+
+        /// Claims position 0 for each account and returns how many were paid. Accounts
+        /// without a stake, with no elapsed period or that the pool can't cover are skipped.
+        #[ink(message)]
+        pub fn claim_for_many(&mut self, accounts: Vec<AccountId>) -> Result<u32, StakingError> {
+            self._only_operator()?;
+            self._non_reentrant(|this| {
+                let mut paid = 0;
+                for account in accounts {
+                    if this._collect_or_skip(account, 0)? {
+                        this._bump_nonce(account);
+                        paid += 1;
+                    }
+                }
+                Ok(paid)
+            })
+        }
+
+        /// Pulls `amount` reward tokens from the caller, who must have approved this contract.
+        #[ink(message)]
+        pub fn fund_rewards_psp22(&mut self, amount: u128) -> Result<(), StakingError> {
+            if amount == 0 {
+                return Err(StakingError::ZeroAmount);
+            }
+            let caller = self.env().caller();
+            self._non_reentrant(|this| {
+                this._transfer_reward_in(caller, amount)?;
+                this.reward_token_balance += amount;
+                this.env().emit_event(RewardTokensFunded { amount });
+                Ok(())
+            })
+        }
+
+        #[ink(message, payable)]
+        pub fn update_rewards_pool(&mut self) -> Result<(), StakingError> {
+            let value = self.env().transferred_value();
+            if value == 0 {
+                return Err(StakingError::ZeroAmount);
+            }
+            let balance = self.rewards_balance.checked_add(value).ok_or(StakingError::Overflow)?;
+            if self.max_rewards_balance != 0 && balance > self.max_rewards_balance {
+                return Err(StakingError::PoolCapExceeded);
+            }
+            self.rewards_balance = balance;
+            self.env().emit_event(RewardPoolUpdated {
+                funder: self.env().caller(),
+                amount: value,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), StakingError> {
+            self._only_owner()?;
+            let previous = self.owner;
+            self.owner = new_owner;
+            self.env().emit_event(OwnershipTransferred {
+                previous,
+                new: new_owner,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_operator(&mut self, who: AccountId) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.operators.insert(who, &true);
+            self.env().emit_event(OperatorAdded { account: who });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_operator(&mut self, who: AccountId) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.operators.remove(who);
+            self.env().emit_event(OperatorRemoved { account: who });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_operator(&self, who: AccountId) -> bool {
+            self.operators.get(who).unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn reward_rate(&self) -> u128 {
+            self.reward_rate
+        }
+
+        /// Rate changes apply prospectively: the next reward calculation pays all of
+        /// its periods at the new rate. With a `rate_change_delay` the rate is only
+        /// scheduled, and `apply_pending_rate` puts it in place once the delay is over.
+        #[ink(message)]
+        pub fn set_reward_rate(&mut self, new_rate: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._schedule_rate_change(RateChange::RewardRate(new_rate))
+        }
+
+        /// Puts every scheduled rate change that is due in place. Anyone may call it. A change
+        /// that the ones applied before it pushed over `max_reward_rate` is dropped instead.
+        #[ink(message)]
+        pub fn apply_pending_rate(&mut self) -> Result<(), StakingError> {
+            let pending = self.pending_rate_changes();
+            if pending.is_empty() {
+                return Err(StakingError::NoPendingRate);
+            }
+            let now = self.env().block_timestamp();
+            let (due, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|(_, effective_at)| *effective_at <= now);
+            if due.is_empty() {
+                return Err(StakingError::TooEarly);
+            }
+            self.pending_rate_changes.set(&waiting);
+            for (change, _) in due {
+                // Each passed on its own when scheduled, not necessarily together.
+                if self._check_rate_ceiling(&change).is_err() {
+                    self.env().emit_event(RateChangeDropped { change });
+                    continue;
+                }
+                self._apply_rate_change(change);
+            }
+            Ok(())
+        }
+
+        /// Scheduled `reward_rate` and when it becomes due, see `pending_rate_changes`.
+        #[ink(message)]
+        pub fn pending_reward_rate(&self) -> Option<(u128, u64)> {
+            self.pending_rate_changes().into_iter().find_map(|(change, effective_at)| match change {
+                RateChange::RewardRate(rate) => Some((rate, effective_at)),
+                _ => None,
+            })
+        }
+
+        #[ink(message)]
+        pub fn pending_rate_changes(&self) -> Vec<(RateChange, u64)> {
+            self.pending_rate_changes.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn max_reward_rate(&self) -> u128 {
+            self.max_reward_rate
+        }
+
+        #[ink(message)]
+        pub fn reward_token(&self) -> AccountId {
+            self.reward_token
+        }
+
+        /// Takes `amount` out of the reward pool and pays it to the owner in reward tokens.
+        #[ink(message)]
+        pub fn withdraw_rewards_surplus(&mut self, amount: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if amount > self.rewards_balance {
+                return Err(StakingError::InsufficientRewards);
+            }
+            self._non_reentrant(|this| {
+                this.rewards_balance -= amount;
+                let owner = this.owner;
+                this._pay_reward(owner, this._reward_token_amount(amount)?)?;
+                this.env().emit_event(RewardSurplusWithdrawn { amount });
+                Ok(())
+            })
+        }
+
+        /// Only allowed while the pool is empty, so no balance is left in the old token.
+        #[ink(message)]
+        pub fn set_reward_token(&mut self, token: AccountId) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if self.rewards_balance > 0 || self.reward_token_balance > 0 {
+                return Err(StakingError::RewardPoolNotEmpty);
+            }
+            let old = self.reward_token;
+            self.reward_token = token;
+            self.env().emit_event(RewardTokenChanged { old, new: token });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn reward_conversion_rate(&self) -> u128 {
+            self.reward_conversion_rate
+        }
+
+        /// Reward-token units paid per unit of `rewards_balance`.
+        #[ink(message)]
+        pub fn set_reward_conversion_rate(&mut self, rate: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if rate == 0 {
+                return Err(StakingError::InvalidConversionRate);
+            }
+            let old = self.reward_conversion_rate;
+            self.reward_conversion_rate = rate;
+            self._param_changed(Param::ConversionRate, old, rate);
+            self.env().emit_event(ConversionRateChanged { old, new: rate });
+            Ok(())
+        }
+
+        /// `(effective_from, rate)` entries in ascending time order. Each rate replaces
+        /// `reward_rate` for periods starting at or after its timestamp. Scheduled like
+        /// `set_reward_rate`.
+        #[ink(message)]
+        pub fn set_rate_schedule(&mut self, schedule: Vec<(u64, u128)>) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if schedule.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                return Err(StakingError::InvalidSchedule);
+            }
+            self._schedule_rate_change(RateChange::RateSchedule(schedule))
+        }
+
+        #[ink(message)]
+        pub fn rate_schedule(&self) -> Vec<(u64, u128)> {
+            self.rate_schedule.clone()
+        }
+
+        /// Rewards pending across every position of every tracked staker, to compare with
+        /// `rewards_balance`. Walks all of `stakers`, so meant for off-chain queries.
+        #[ink(message)]
+        pub fn total_pending_rewards(&self) -> u128 {
+            (0..self.staker_count)
+                .filter_map(|index| self.stakers.get(index))
+                .flat_map(|account| (0..self.position_count(account)).map(move |position| (account, position)))
+                .filter_map(|(account, position)| self.reward_amount(account, position).ok())
+                .fold(0u128, |total, (_, reward)| total.saturating_add(reward))
+        }
+
+        /// `reward_token` balance the token itself reports for this contract, to reconcile
+        /// with `reward_token_balance` after direct transfers in.
+        #[ink(message)]
+        pub fn reward_token_onchain_balance(&self) -> u128 {
+            self._reward_token_onchain_balance()
+        }
+
+        /// Principal held by the contract next to the principal it owes. Stakes are paid
+        /// in `staking_token`, so the held side is that token's balance, not native value.
+        #[ink(message)]
+        pub fn solvency(&self) -> (Balance, u128) {
+            (self._staking_token_balance(), self.total_staked)
+        }
+
+        /// Reward periods `rewards_balance` lasts at the current emission of all staked
+        /// principal, expired positions included. `u128::MAX` when nothing is emitted.
+        #[ink(message)]
+        pub fn pool_runway_days(&self) -> u128 {
+            let now = self.env().block_timestamp();
+            // `stake_days` positions sit in period 0.
+            let day_locks = (!self.available_periods.contains(&0)).then_some(0);
+            let emission = self.available_periods.iter().copied().chain(day_locks)
+                .map(|period| {
+                    let rate = self._rate_sum(period, now, 1).unwrap_or(self.reward_rate);
+                    let multiplier = self.period_multipliers.get(period).unwrap_or(100);
+                    self.staked_in_period(period).saturating_mul(rate).saturating_mul(multiplier) / 36000
+                })
+                .fold(0u128, u128::saturating_add);
+            if emission == 0 {
+                return u128::MAX;
+            }
+            self.rewards_balance / emission
+        }
+
+        #[ink(message)]
+        pub fn available_periods(&self) -> Vec<u32> {
+            self.available_periods.clone()
+        }
+
+        /// Periods unlocked by `level`, e.g. only level 2 may pick 12.
+        #[ink(message)]
+        pub fn set_level_periods(&mut self, level: u32, periods: Vec<u32>) -> Result<(), StakingError> {
+            self._only_owner()?;
+            for period in &periods {
+                self._validate_period(*period)?;
+            }
+            self.level_periods.insert(level, &periods);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn level_periods(&self, level: u32) -> Vec<u32> {
+            self.level_periods.get(level).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn set_level(&mut self, account: AccountId, level: u32) -> Result<(), StakingError> {
+            self._only_operator()?;
+            self._bump_nonce(account);
+            self.levels.insert(account, &level);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn level_of(&self, account: AccountId) -> u32 {
+            self.levels.get(account).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn add_period(&mut self, period: u32) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if self.available_periods.contains(&period) {
+                return Err(StakingError::PeriodExists);
+            }
+            self.available_periods.push(period);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_period(&mut self, period: u32) -> Result<(), StakingError> {
+            self._only_owner()?;
+            // Not `_validate_period`: periods above `max_period` must stay removable.
+            if !self.available_periods.contains(&period) {
+                return Err(StakingError::PeriodNotFound);
+            }
+            if self.staked_in_period(period) > 0 {
+                return Err(StakingError::PeriodInUse);
+            }
+            self.available_periods.retain(|p| *p != period);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn early_withdraw_fee(&self) -> u128 {
+            self.early_withdraw_fee
+        }
+
+        /// Percent of the principal kept from exits before `active_until`.
+        #[ink(message)]
+        pub fn set_early_withdraw_fee(&mut self, fee_percent: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if fee_percent > 100 {
+                return Err(StakingError::InvalidFee);
+            }
+            let old = self.early_withdraw_fee;
+            self.early_withdraw_fee = fee_percent;
+            self._param_changed(Param::EarlyWithdrawFee, old, fee_percent);
+            self.env().emit_event(EarlyWithdrawFeeChanged { old, new: fee_percent });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_fee_recipient(&mut self, recipient: AccountId) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.fee_recipient = recipient;
+            Ok(())
+        }
+
+        /// Caps `total_staked`, 0 means unlimited.
+        #[ink(message)]
+        pub fn set_max_total_staked(&mut self, max: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::MaxTotalStaked, self.max_total_staked, max);
+            self.max_total_staked = max;
+            Ok(())
+        }
+
+        /// Holders of at least one token of the PSP34 `contract` earn `bps` / 10000 of the
+        /// base reward. Only checked in builds with the `booster` feature. Scheduled like
+        /// `set_reward_rate`.
+        #[ink(message)]
+        pub fn set_booster(&mut self, contract: Option<AccountId>, bps: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._schedule_rate_change(RateChange::Booster(contract, bps))
+        }
+
+        /// Longest period that may be staked for, 0 means any listed period.
+        #[ink(message)]
+        pub fn set_max_period(&mut self, max: u32) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::MaxPeriod, self.max_period as u128, max as u128);
+            self.max_period = max;
+            Ok(())
+        }
+
+        /// Shortest period that may be staked or extended for, 0 means any listed period.
+        #[ink(message)]
+        pub fn set_min_period(&mut self, min: u32) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::MinPeriod, self.min_period as u128, min as u128);
+            self.min_period = min;
+            Ok(())
+        }
+
+        /// Caps `rewards_balance` against accidental over-funding, 0 means unlimited.
+        #[ink(message)]
+        pub fn set_max_rewards_balance(&mut self, max: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::MaxRewardsBalance, self.max_rewards_balance, max);
+            self.max_rewards_balance = max;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_min_stake(&mut self, min: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._param_changed(Param::MinStake, self.min_stake, min);
+            self.min_stake = min;
+            Ok(())
+        }
+
+        /// Length of one reward period in seconds. Lock durations stay in 30-day months.
+        #[ink(message)]
+        pub fn set_reward_period(&mut self, secs: u64) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if secs == 0 {
+                return Err(StakingError::InvalidRewardPeriod);
+            }
+            self._param_changed(Param::RewardPeriod, self.reward_period_secs as u128, secs as u128);
+            self.reward_period_secs = secs;
+            Ok(())
+        }
+
+        /// `bps` is relative to 100, so 200 pays twice the base rate for that period.
+        /// Scheduled like `set_reward_rate`.
+        #[ink(message)]
+        pub fn set_period_multiplier(&mut self, period: u32, bps: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._validate_period(period)?;
+            self._schedule_rate_change(RateChange::PeriodMultiplier(period, bps))
+        }
+
+        /// Base rate for positions locked for `period`, in place of `reward_rate`.
+        /// Scheduled like `set_reward_rate`.
+        #[ink(message)]
+        pub fn set_period_rate(&mut self, period: u32, rate: u128) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self._validate_period(period)?;
+            self._schedule_rate_change(RateChange::PeriodRate(period, rate))
+        }
+
+        /// Blocks new stakes and extends; claims and withdrawals keep working.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.paused = true;
+            self.env().emit_event(Paused { account: self.env().caller() });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.paused = false;
+            self.env().emit_event(Unpaused { account: self.env().caller() });
+            Ok(())
+        }
+
+        /// Ends the program for good: no new stakes, and every position may leave through
+        /// `withdraw` or `emergency_withdraw` without the early fee, whatever its lock.
+        #[ink(message)]
+        pub fn begin_winddown(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if !self.winddown {
+                self.winddown = true;
+                self.env().emit_event(WinddownStarted { account: self.env().caller() });
+            }
+            Ok(())
+        }
+
+        /// Rounds reward division up instead of down, so small positions earn at least one
+        /// unit per claim. Each claim may then pay up to one unit more than accrued, which
+        /// `rewards_balance` has to cover on top of the exact emission.
+        #[ink(message)]
+        pub fn set_round_up_rewards(&mut self, round_up: bool) -> Result<(), StakingError> {
+            self._only_owner()?;
+            self.round_up_rewards = round_up;
+            Ok(())
+        }
+
+        /// Stops reward accrual without blocking stakes or withdrawals.
+        #[ink(message)]
+        pub fn pause_rewards(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if !self.rewards_paused {
+                self.rewards_paused = true;
+                self.rewards_paused_at = self.env().block_timestamp();
+                self.env().emit_event(RewardsPaused { account: self.env().caller() });
+            }
+            Ok(())
+        }
+
+        /// Records the paused window in `reward_pauses`, which reward math leaves out, so it
+        /// is never credited. No reward clock is touched here.
+        #[ink(message)]
+        pub fn resume_rewards(&mut self) -> Result<(), StakingError> {
+            self._only_owner()?;
+            if !self.rewards_paused {
+                return Ok(());
+            }
+            let now = self.env().block_timestamp();
+            self.reward_pauses.insert(self.reward_pause_count, &(self.rewards_paused_at, now));
+            self.reward_pause_count += 1;
+            self.rewards_paused = false;
+            self.env().emit_event(RewardsResumed { account: self.env().caller() });
+            Ok(())
+        }
+
+        /// Rejects nested entry into messages that make external calls. The flag lives in
+        /// its own storage cell, so it is already written when a callee re-enters.
+        fn _non_reentrant<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, StakingError>) -> Result<T, StakingError> {
+            if self.locked.get().unwrap_or(false) {
+                return Err(StakingError::Reentrant);
+            }
+            self.locked.set(&true);
+            let result = f(self);
+            self.locked.set(&false);
+            result
+        }
+
+        /// Whether exits of this position still count as early.
+        fn _is_locked(&self, stake_info: &StakeInfo) -> bool {
+            !self.winddown && self.env().block_timestamp() < stake_info.active_until
+        }
+
+        fn _when_not_paused(&self) -> Result<(), StakingError> {
+            if self.paused {
+                return Err(StakingError::Paused);
+            }
+            Ok(())
+        }
+
+        fn _only_owner(&self) -> Result<(), StakingError> {
+            if self.env().caller() != self.owner {
+                return Err(StakingError::NotOwner);
+            }
+            Ok(())
+        }
+
+        fn _only_operator(&self) -> Result<(), StakingError> {
+            if !self.is_operator(self.env().caller()) {
+                return Err(StakingError::NotOperator);
+            }
+            Ok(())
+        }
+
+        fn _validate_period(&self, period: u32) -> Result<(), StakingError> {
+            if !self.available_periods.contains(&period) {
+                return Err(StakingError::PeriodNotFound);
+            }
+            if self.max_period > 0 && period > self.max_period {
+                return Err(StakingError::PeriodTooLong);
+            }
+            if period < self.min_period {
+                return Err(StakingError::PeriodTooShort);
+            }
+            Ok(())
+        }
+
+        /// Applies `change` right away without a `rate_change_delay`, otherwise queues it in
+        /// place of any pending change of the same setting.
+        fn _schedule_rate_change(&mut self, change: RateChange) -> Result<(), StakingError> {
+            self._check_rate_ceiling(&change)?;
+            if self.rate_change_delay == 0 {
+                self._apply_rate_change(change);
+                return Ok(());
+            }
+            let effective_at = self.env().block_timestamp().saturating_add(self.rate_change_delay);
+            let mut pending = self.pending_rate_changes();
+            pending.retain(|(queued, _)| !change.replaces(queued));
+            pending.push((change.clone(), effective_at));
+            self.pending_rate_changes.set(&pending);
+            self.env().emit_event(RateChangeScheduled { change, effective_at });
+            Ok(())
+        }
+
+        fn _apply_rate_change(&mut self, change: RateChange) {
+            match change {
+                RateChange::RewardRate(new_rate) => {
+                    let old = self.reward_rate;
+                    self.reward_rate = new_rate;
+                    self._param_changed(Param::RewardRate, old, new_rate);
+                    self.env().emit_event(RewardRateChanged { old, new: new_rate });
+                }
+                RateChange::PeriodMultiplier(period, bps) => {
+                    self._param_changed(Param::PeriodMultiplier, self.period_multipliers.get(period).unwrap_or(100), bps);
+                    self.period_multipliers.insert(period, &bps);
+                }
+                RateChange::PeriodRate(period, rate) => {
+                    self._param_changed(Param::PeriodRate, self.period_reward_rate.get(period).unwrap_or(0), rate);
+                    self.period_reward_rate.insert(period, &rate);
+                }
+                RateChange::RateSchedule(schedule) => {
+                    self.rate_schedule = schedule;
+                }
+                RateChange::Booster(contract, bps) => {
+                    self.booster_contract = contract;
+                    self._param_changed(Param::BoosterBps, self.booster_bps, bps);
+                    self.booster_bps = bps;
+                }
+            }
+        }
+
+        /// Rejects `change` if some period would then earn more than `max_reward_rate` per
+        /// period, counting its multiplier and a booster bonus on top of the base rate.
+        fn _check_rate_ceiling(&self, change: &RateChange) -> Result<(), StakingError> {
+            let reward_rate = match change {
+                RateChange::RewardRate(rate) => *rate,
+                _ => self.reward_rate,
+            };
+            let schedule = match change {
+                RateChange::RateSchedule(schedule) => schedule,
+                _ => &self.rate_schedule,
+            };
+            let base_rate = schedule.iter().map(|(_, rate)| *rate).fold(reward_rate, u128::max);
+            let (booster, booster_bps) = match change {
+                RateChange::Booster(contract, bps) => (*contract, *bps),
+                _ => (self.booster_contract, self.booster_bps),
+            };
+            let boost = if booster.is_some() { booster_bps.max(10000) } else { 10000 };
+            // `stake_days` positions sit in period 0.
+            for period in self.available_periods.iter().copied().chain(Some(0)) {
+                let rate = match change {
+                    RateChange::PeriodRate(changed, rate) if *changed == period => *rate,
+                    _ => self.period_reward_rate.get(period).unwrap_or(base_rate),
+                };
+                let multiplier = match change {
+                    RateChange::PeriodMultiplier(changed, bps) if *changed == period => *bps,
+                    _ => self.period_multipliers.get(period).unwrap_or(100),
+                };
+                if rate.saturating_mul(multiplier).saturating_mul(boost) > self.max_reward_rate.saturating_mul(100 * 10000) {
+                    return Err(StakingError::RateAboveMax);
+                }
+            }
+            Ok(())
+        }
+
+        fn _param_changed(&self, key: Param, old: u128, new: u128) {
+            self.env().emit_event(ParamChanged { key: key as u8, old, new });
+        }
+
+        fn _check_unbonded(&self, account: AccountId, position: u32, amount: u128) -> Result<(), StakingError> {
+            if self.large_threshold == 0 || amount <= self.large_threshold {
+                return Ok(());
+            }
+            match self.withdraw_ready_at.get((account, position)) {
+                None => Err(StakingError::WithdrawNotRequested),
+                Some(ready_at) if self.env().block_timestamp() < ready_at => Err(StakingError::Unbonding),
+                Some(_) => Ok(()),
+            }
+        }
+
+        /// Levels without `level_periods` may use every listed period.
+        fn _check_level(&self, account: AccountId, period: u32) -> Result<(), StakingError> {
+            match self.level_periods.get(self.level_of(account)) {
+                Some(allowed) if !allowed.contains(&period) => Err(StakingError::PeriodNotAllowed),
+                _ => Ok(()),
+            }
+        }
+
+        fn reward_amount(&self, account: AccountId, position: u32) -> Result<(u32, u128), StakingError> {
+            self._reward_at(account, position, self.env().block_timestamp())
+        }
+
+        /// `reward_amount` as it would be at `timestamp`.
+        fn _reward_at(&self, account: AccountId, position: u32, timestamp: u64) -> Result<(u32, u128), StakingError> {
+            let stake_info = self.stakes.get((account, position)).ok_or(StakingError::NoStake)?;
+            // What accrued before a running pause stays payable.
+            let timestamp = if self.rewards_paused { timestamp.min(self.rewards_paused_at) } else { timestamp };
+            let time = timestamp.min(stake_info.active_until);
+            let accrued_from = self._accrued_from(account, position, &stake_info);
+            // Fully claimed, or the position was closed after the last claim.
+            if accrued_from >= stake_info.active_until {
+                return Ok((0, 0));
+            }
+            let elapsed = time.saturating_sub(accrued_from) - self._paused_between(accrued_from, time);
+            let periods_passed = elapsed / self.reward_period_secs;
+            // Periods a skipped collect already priced keep that price, whatever the principal now.
+            let (owed, owed_periods) = match self.owed_rewards.get((account, position)) {
+                Some((owed, owed_periods)) if owed_periods as u64 <= periods_passed => (owed, owed_periods as u64),
+                _ => (0, 0),
+            };
+            let from = self._unpaused_after(accrued_from, owed_periods * self.reward_period_secs);
+            // The multiplier takes the place of the former flat `* 100`.
+            let multiplier = self.period_multipliers.get(stake_info.period).unwrap_or(100);
+            // Divide last to keep the rounding of the per-day rates, but fail instead of trapping.
+            let reward = self._rate_sum(stake_info.period, from, periods_passed - owed_periods)
+                .and_then(|rates| stake_info.amount.checked_mul(rates))
+                .and_then(|value| value.checked_mul(multiplier))
+                .ok_or(StakingError::RewardOverflow)?;
+            let reward = self._boosted(account, self._scale_reward(reward))?;
+            let reward = reward.checked_add(owed).ok_or(StakingError::RewardOverflow)?;
+            Ok((periods_passed as u32, reward))
+        }
+
+        /// `reward` with the booster bonus applied if `account` holds a booster token.
+        fn _boosted(&self, account: AccountId, reward: u128) -> Result<u128, StakingError> {
+            match self.booster_contract {
+                Some(booster) if reward > 0 && self._holds_booster(booster, account) => {
+                    Ok(reward.checked_mul(self.booster_bps).ok_or(StakingError::RewardOverflow)? / 10000)
+                }
+                _ => Ok(reward),
+            }
+        }
+
+        /// Moves the reward clock of the position to `at`, which settles its `owed_rewards`.
+        fn _checkpoint(&mut self, account: AccountId, position: u32, at: u64) {
+            self.last_reward_claims.insert((account, position), &at);
+            self.owed_rewards.remove((account, position));
+        }
+
+        fn _use_nonce(&mut self, account: AccountId, expected: u64) -> Result<(), StakingError> {
+            let nonce = self.nonce_of(account);
+            if nonce != expected {
+                return Err(StakingError::StaleNonce);
+            }
+            self._bump_nonce(account);
+            Ok(())
+        }
+
+        fn _bump_nonce(&mut self, account: AccountId) {
+            self.action_nonce.insert(account, &(self.nonce_of(account) + 1));
+        }
+
+        /// Turns `amount * rate * multiplier` into reward units, see `set_round_up_rewards`.
+        fn _scale_reward(&self, value: u128) -> u128 {
+            if self.round_up_rewards {
+                value.div_ceil(36000)
+            } else {
+                value / 36000
+            }
+        }
+
+        /// Sum of the rates in effect at the start of each of `periods` reward periods from `from`.
+        /// A `period_reward_rate` of the lock period replaces both `reward_rate` and the schedule.
+        fn _rate_sum(&self, period: u32, from: u64, periods: u64) -> Option<u128> {
+            if let Some(rate) = self.period_reward_rate.get(period) {
+                return rate.checked_mul(periods as u128);
+            }
+            let mut total: u128 = 0;
+            let mut rate = self.reward_rate;
+            let mut counted = 0;
+            for &(effective_from, next_rate) in &self.rate_schedule {
+                // Periods starting before `effective_from` still use `rate`.
+                let before = effective_from.saturating_sub(from).div_ceil(self.reward_period_secs).min(periods);
+                if before > counted {
+                    total = total.checked_add(rate.checked_mul((before - counted) as u128)?)?;
+                    counted = before;
+                }
+                rate = next_rate;
+            }
+            total.checked_add(rate.checked_mul((periods - counted) as u128)?)
+        }
+
+        /// Start of the unclaimed window. `_stake` resets it whenever a lock starts, the
+        /// fallback only covers positions opened before that.
+        fn _accrued_from(&self, account: AccountId, position: u32, stake_info: &StakeInfo) -> u64 {
+            self.last_reward_claims.get((account, position)).unwrap_or(stake_info.started_at)
+        }
+
+        /// Uses the same window as `reward_amount`, so the date never lies past `active_until`.
+        fn _next_reward_date(&self, account: AccountId, position: u32) -> Result<u64, StakingError> {
+            let stake_info = self.stakes.get((account, position)).ok_or(StakingError::NoStake)?;
+            let now = self.env().block_timestamp();
+            if now >= stake_info.active_until {
+                return Ok(stake_info.active_until);
+            }
+            let accrued_from = self._accrued_from(account, position, &stake_info);
+            let elapsed = now.saturating_sub(accrued_from) - self._paused_between(accrued_from, now);
+            let passed_periods = elapsed / self.reward_period_secs;
+            Ok(self._unpaused_after(accrued_from, (passed_periods + 1) * self.reward_period_secs).min(stake_info.active_until))
+        }
+
+        /// Time inside `[from, to)` covered by `reward_pauses`. Walks back from the latest
+        /// pause, so only the pauses since `from` are read.
+        fn _paused_between(&self, from: u64, to: u64) -> u64 {
+            let mut paused = 0;
+            for index in (0..self.reward_pause_count).rev() {
+                let Some((start, end)) = self.reward_pauses.get(index) else { continue };
+                if end <= from {
+                    break;
+                }
+                paused += end.min(to).saturating_sub(start.max(from));
+            }
+            paused
+        }
+
+        /// The time `secs` of unpaused time after `from`, skipping over `reward_pauses`.
+        fn _unpaused_after(&self, from: u64, secs: u64) -> u64 {
+            let mut pauses: Vec<(u64, u64)> = (0..self.reward_pause_count).rev()
+                .map_while(|index| self.reward_pauses.get(index).filter(|(_, end)| *end > from))
+                .collect();
+            pauses.reverse();
+            let (mut at, mut left) = (from, secs);
+            for (start, end) in pauses {
+                let start = start.max(at);
+                if at + left <= start {
+                    break;
+                }
+                left -= start - at;
+                at = end;
+            }
+            at + left
+        }
+
+        fn _stake_from(&mut self, payer: AccountId, account: AccountId, period: u32, amount: u128, reset_lock: bool) -> Result<u128, StakingError> {
+            self._when_not_paused()?;
+            if amount == 0 {
+                return Err(StakingError::ZeroAmount);
+            }
+            if amount < self.min_stake {
+                return Err(StakingError::BelowMinStake);
+            }
+
+            let previous_amount = self.stakes.get((account, 0)).map(|info| info.amount).unwrap_or(0);
+            if previous_amount != 0 {
+                self._collect_or_skip(account, 0)?;
+            }
+            self._transfer_stake_in(payer, amount)?;
+            self._stake(account, 0, period, amount, reset_lock)
+        }
+
+        /// Opens `position` of `account` with `amount` pulled from `payer`.
+        fn _open_position(&mut self, payer: AccountId, account: AccountId, position: u32, period: u32, amount: u128) -> Result<u32, StakingError> {
+            self._when_not_paused()?;
+            if amount == 0 {
+                return Err(StakingError::ZeroAmount);
+            }
+            if amount < self.min_stake {
+                return Err(StakingError::BelowMinStake);
+            }
+            self._transfer_stake_in(payer, amount)?;
+            self._stake(account, position, period, amount, false)?;
+            Ok(position)
+        }
+
+        fn _stake(&mut self, account: AccountId, position: u32, periods: u32, amount: u128, reset_lock: bool) -> Result<u128, StakingError> {
+            self._validate_period(periods)?;
+            self._check_level(account, periods)?;
+            self._stake_locked(account, position, periods, periods as u64 * 86400 * 30, amount, reset_lock)
+        }
+
+        /// `_stake` with the lock length given in seconds, for month and day locks alike.
+        fn _stake_locked(&mut self, account: AccountId, position: u32, periods: u32, lock_secs: u64, amount: u128, reset_lock: bool) -> Result<u128, StakingError> {
+            if self.winddown {
+                return Err(StakingError::Winddown);
+            }
+            let new_amount = self.stakes.get((account, position))
+                .map_or(Some(amount), |info| info.amount.checked_add(amount))
+                .ok_or(StakingError::Overflow)?;
+            let total_staked = self.total_staked.checked_add(amount).ok_or(StakingError::Overflow)?;
+            if self.max_total_staked != 0 && total_staked > self.max_total_staked {
+                return Err(StakingError::CapExceeded);
+            }
+            // Top-ups keep the longer period and at least the running lock unless `reset_lock`,
+            // fresh positions, extends and top-ups of an expired lock start a new one. Only the
+            // latter restart the reward clock.
+            let now = self.env().block_timestamp();
+            let lock_until = now + lock_secs;
+            let (period, started_at, until) = match self.stakes.get((account, position)) {
+                Some(stake_info) if amount != 0 && stake_info.amount != 0 => {
+                    if now >= stake_info.active_until {
+                        // Nothing accrued past the old end, so the gap must not be paid either.
+                        let owed = self.owed_rewards.get((account, position)).map_or(0, |(owed, _)| owed);
+                        self._checkpoint(account, position, now);
+                        if owed > 0 {
+                            self.owed_rewards.insert((account, position), &(owed, 0));
+                        }
+                        (periods, now, lock_until)
+                    } else if reset_lock {
+                        (periods, now, lock_until)
+                    } else {
+                        let until = self._merged_until(&stake_info, amount, lock_until, now)?;
+                        (periods.max(stake_info.period), stake_info.started_at, until)
+                    }
+                }
+                _ => {
+                    self._checkpoint(account, position, now);
+                    (periods, now, lock_until)
+                }
+            };
+            if position >= self.position_count.get(account).unwrap_or(0) {
+                self.position_count.insert(account, &(position + 1));
+            }
+
+            self._set_stake_info(account, position, new_amount, period, started_at, until)?;
+            self.total_staked = total_staked;
+            let event = if amount == 0 {
+                StakeEvent::Extended { timestamp: now, amount: new_amount }
+            } else {
+                StakeEvent::Staked { timestamp: now, amount }
+            };
+            self._record(account, event);
+            self.env().emit_event(Stake {
+                account,
+                staked_at: self.env().block_timestamp(),
+                period,
+                sum: amount,
+                total_staked: new_amount,
+            });
+            Ok(new_amount)
+        }
+
+        /// Lock end of a top-up of `amount` into `old`, weighting the remaining durations by
+        /// principal:
+        ///
+        /// ```text
+        /// now + (old.amount * (old.active_until - now) + amount * (lock_until - now)) / (old.amount + amount)
+        /// ```
+        ///
+        /// The result never comes before `old.active_until`, so a short deposit can't cut the
+        /// running lock. Top-ups of an expired lock start a new one in `_stake_locked` instead.
+        fn _merged_until(&self, old: &StakeInfo, amount: u128, lock_until: u64, now: u64) -> Result<u64, StakingError> {
+            let old_weight = old.amount.checked_mul(old.active_until.saturating_sub(now) as u128);
+            let new_weight = amount.checked_mul(lock_until.saturating_sub(now) as u128);
+            let remaining = old_weight
+                .zip(new_weight)
+                .and_then(|(old_weight, new_weight)| old_weight.checked_add(new_weight))
+                .and_then(|weight| weight.checked_div(old.amount.checked_add(amount)?))
+                .ok_or(StakingError::Overflow)?;
+            Ok((now + remaining as u64).max(old.active_until))
+        }
+
+        /// Early exits are charged `early_withdraw_fee` percent. Returns `(fee, payout)`.
+        fn _withdraw_split(&self, amount: u128, is_early: bool) -> Result<(u128, u128), StakingError> {
+            let fee = if is_early { amount.checked_mul(self.early_withdraw_fee).ok_or(StakingError::Overflow)? / 100 } else { 0 };
+            Ok((fee, amount - fee))
+        }
+
+        /// The fee from `_withdraw_split` goes to `fee_recipient`.
+        ///
+        /// A failed transfer must surface as `Err`: ink reverts every storage write and event
+        /// of a message that returns `Err`, which restores the position zeroed above. Never
+        /// swallow a transfer error here, the recorded stake would be lost with it.
+        fn _withdraw(&mut self, account: AccountId, position: u32, amount: u128, is_early: bool) -> Result<(), StakingError> {
+            let (fee, payout) = self._withdraw_split(amount, is_early)?;
+            // All state is settled before any tokens leave the contract.
+            self._set_stake_info(account, position, 0, 0, 0, 0)?;
+            self.withdraw_ready_at.remove((account, position));
+            self.total_staked = self.total_staked.saturating_sub(amount);
+            self._record(account, StakeEvent::Withdrew { timestamp: self.env().block_timestamp(), amount: payout });
+            let recipient = self.fee_recipient;
+            if fee > 0 {
+                self.env().emit_event(FeeCollected {
+                    from: account,
+                    amount: fee,
+                    recipient,
+                });
+            }
+            self.env().emit_event(Withdraw {
+                account,
+                sum: payout,
+                is_early,
+            });
+            if fee > 0 {
+                self._transfer_stake_out(recipient, fee)?;
+            }
+            self._transfer_stake_out(account, payout)
+        }
+
+        fn _collect_rewards(&mut self, account: AccountId, position: u32, not_direct: bool) -> Result<(), StakingError> {
+            if let Some(stake_info) = self.stakes.get((account, position)) {
+                if stake_info.amount > 0 {
+                    let (periods, reward) = self.reward_amount(account, position)?;
+                    if periods == 0 {
+                        // Direct claims confirm with an empty `Claim` instead of failing.
+                        if !not_direct {
+                            self.env().emit_event(Claim { account, periods: 0, amount: 0 });
+                        }
+                        return Ok(());
+                    }
+                    let bonus = self._referral_bonus(account, reward)?;
+                    if !self._covers(reward + bonus) {
+                        return Err(StakingError::InsufficientRewards);
+                    }
+                    // Same conditions as `compound`, otherwise the reward is paid out as usual.
+                    if not_direct && self.auto_compound(account) && !self.winddown && self.reward_token == self.staking_token
+                        && self._reward_token_amount(reward).is_ok_and(|amount| self._within_cap(amount))
+                    {
+                        return self._compound_into(account, position, periods, reward);
+                    }
+                    let reward_amount_in_reward_token = self._settle_rewards(account, position, &stake_info, periods, reward)?;
+                    self.env().emit_event(Claim {
+                        account,
+                        periods,
+                        amount: reward,
+                    });
+                    // Transfer the reward tokens to the account using the PSP22 interface
+                    self._pay_reward(account, reward_amount_in_reward_token)?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Books `reward` for `periods` as principal of the position. Coverage is checked by
+        /// the caller, `max_total_staked` here.
+        fn _compound_into(&mut self, account: AccountId, position: u32, periods: u32, reward: u128) -> Result<(), StakingError> {
+            let stake_info = self.stakes.get((account, position)).ok_or(StakingError::NoStake)?;
+            let amount = self._reward_token_amount(reward)?;
+            if !self._within_cap(amount) {
+                return Err(StakingError::CapExceeded);
+            }
+            self._settle_rewards(account, position, &stake_info, periods, reward)?;
+            // The tokens stay in the contract, but now back principal instead of rewards.
+            self.reward_token_balance -= amount;
+            self._set_stake_info(account, position, stake_info.amount + amount, stake_info.period, stake_info.started_at, stake_info.active_until)?;
+            self.total_staked += amount;
+            self._record(account, StakeEvent::Staked { timestamp: self.env().block_timestamp(), amount });
+            self.env().emit_event(Compounded { account, amount });
+            Ok(())
+        }
+
+        /// Books `reward` for `periods` of the position as collected: moves its reward clock,
+        /// charges the budget and credits the referrer. Returns the reward tokens it is worth,
+        /// which the caller pays out or restakes. Coverage is checked by the caller.
+        fn _settle_rewards(&mut self, account: AccountId, position: u32, stake_info: &StakeInfo, periods: u32, reward: u128) -> Result<u128, StakingError> {
+            let amount = self._reward_token_amount(reward)?;
+            let accrued_from = self._accrued_from(account, position, stake_info);
+            self._checkpoint(account, position, self._unpaused_after(accrued_from, periods as u64 * self.reward_period_secs));
+            self.rewards_balance -= reward;
+            self.total_rewards_distributed += reward;
+            let bonus = self._referral_bonus(account, reward)?;
+            self._credit_referrer(account, bonus);
+            self._record(account, StakeEvent::Claimed { timestamp: self.env().block_timestamp(), amount: reward });
+            Ok(amount)
+        }
+
+        /// Whether `amount` more principal keeps `total_staked` within `max_total_staked`.
+        fn _within_cap(&self, amount: u128) -> bool {
+            self.total_staked
+                .checked_add(amount)
+                .is_some_and(|total| self.max_total_staked == 0 || total <= self.max_total_staked)
+        }
+
+        /// Whether both the budget and the held reward tokens can pay `reward`.
+        fn _covers(&self, reward: u128) -> bool {
+            self.rewards_balance >= reward
+                && self._reward_token_amount(reward).is_ok_and(|amount| self.reward_token_balance >= amount)
+        }
+
+        /// Sends `amount` reward tokens out of `reward_token_balance`.
+        fn _pay_reward(&mut self, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            self.reward_token_balance = self.reward_token_balance.checked_sub(amount).ok_or(StakingError::InsufficientRewards)?;
+            self._transfer_reward(to, amount)
+        }
+
+        /// Reward-token units for `reward` pool units, `RewardOverflow` if they don't fit a u128.
+        pub(crate) fn _reward_token_amount(&self, reward: u128) -> Result<u128, StakingError> {
+            let scale = 10u128
+                .checked_pow(self.reward_token_decimals.abs_diff(self.native_decimals) as u32)
+                .ok_or(StakingError::RewardOverflow)?;
+            let amount = reward.checked_mul(self.reward_conversion_rate).ok_or(StakingError::RewardOverflow)?;
+            if self.reward_token_decimals >= self.native_decimals {
+                amount.checked_mul(scale).ok_or(StakingError::RewardOverflow)
+            } else {
+                Ok(amount / scale)
+            }
+        }
+
+        fn _add_staker(&mut self, account: AccountId) {
+            if !self.staker_index.contains(account) {
+                self.stakers.insert(self.staker_count, &account);
+                self.staker_index.insert(account, &self.staker_count);
+                self.staker_count += 1;
+            }
+        }
+
+        /// Drops `account` from `stakers`, the last listed account takes over its index.
+        fn _remove_staker(&mut self, account: AccountId) {
+            let Some(index) = self.staker_index.take(account) else { return };
+            self.staker_count -= 1;
+            if let Some(last) = self.stakers.take(self.staker_count) {
+                if index != self.staker_count {
+                    self.stakers.insert(index, &last);
+                    self.staker_index.insert(last, &index);
+                }
+            }
+        }
+
+        fn _record(&mut self, account: AccountId, event: StakeEvent) {
+            let mut history = self.history_of(account);
+            if history.len() >= HISTORY_LEN {
+                history.remove(0);
+            }
+            history.push(event);
+            self.history.insert(account, &history);
+        }
+
+        pub(crate) fn _referral_bonus(&self, account: AccountId, reward: u128) -> Result<u128, StakingError> {
+            if self.referrers.contains(account) {
+                Ok(reward.checked_mul(self.referral_bonus_bps).ok_or(StakingError::RewardOverflow)? / 10000)
+            } else {
+                Ok(0)
+            }
+        }
+
+        /// Moves `bonus` from the pool to the referrer of `account`, see `_referral_bonus`.
+        fn _credit_referrer(&mut self, account: AccountId, bonus: u128) {
+            if let Some(referrer) = self.referrers.get(account) {
+                if bonus > 0 {
+                    self.rewards_balance -= bonus;
+                    self.referral_earnings.insert(referrer, &(self.referral_earnings(referrer) + bonus));
+                }
+            }
+        }
+
+        /// Collects like a non-direct claim, but leaves the rewards pending and emits
+        /// `ClaimSkipped` when the pool can't cover them. The skipped amount is kept in
+        /// `owed_rewards`, so a top-up right after doesn't raise it. Returns whether anything
+        /// was paid.
+        fn _collect_or_skip(&mut self, account: AccountId, position: u32) -> Result<bool, StakingError> {
+            let Ok((periods, owed)) = self.reward_amount(account, position) else {
+                return Ok(false);
+            };
+            if periods == 0 {
+                return Ok(false);
+            }
+            let Ok(bonus) = self._referral_bonus(account, owed) else {
+                return Ok(false);
+            };
+            if !self._covers(owed + bonus) {
+                self.owed_rewards.insert((account, position), &(owed, periods));
+                self.env().emit_event(ClaimSkipped {
+                    account,
+                    owed,
+                    available: self.rewards_balance,
+                });
+                return Ok(false);
+            }
+            self._collect_rewards(account, position, true)?;
+            Ok(true)
+        }
+
+        fn _transfer_reward(&self, account: AccountId, amount: u128) -> Result<(), StakingError> {
+            Tokens::transfer(self.reward_token, account, amount)
+        }
+
+        fn _transfer_stake_in(&self, from: AccountId, amount: u128) -> Result<(), StakingError> {
+            Tokens::transfer_from(self.staking_token, from, self.env().account_id(), amount)
+        }
+
+        fn _transfer_stake_out(&self, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            Tokens::transfer(self.staking_token, to, amount)
+        }
+
+        fn _transfer_reward_in(&self, from: AccountId, amount: u128) -> Result<(), StakingError> {
+            Tokens::transfer_from(self.reward_token, from, self.env().account_id(), amount)
+        }
+
+        fn _reward_token_onchain_balance(&self) -> Balance {
+            Tokens::balance_of(self.reward_token, self.env().account_id())
+        }
+
+        fn _staking_token_balance(&self) -> Balance {
+            Tokens::balance_of(self.staking_token, self.env().account_id())
+        }
+
+        fn _permit(&self, owner: AccountId, amount: u128, deadline: u64, signature: Vec<u8>) -> Result<(), StakingError> {
+            Tokens::permit(self.staking_token, owner, self.env().account_id(), amount, deadline, signature)
+        }
+
+        fn _holds_booster(&self, booster: AccountId, account: AccountId) -> bool {
+            Tokens::holds_booster(booster, account)
+        }
+
+        /// Every position write goes through here, which keeps `staked_by_period`,
+        /// `open_positions` and `stakers` in step.
+        fn _set_stake_info(&mut self, account: AccountId, position: u32, amount: u128, periods: u32, started_at: u64, until: u64) -> Result<(), StakingError> {
+            let mut old_amount = 0;
+            if let Some(old) = self.stakes.get((account, position)) {
+                let bucket = self.staked_in_period(old.period).saturating_sub(old.amount);
+                self.staked_by_period.insert(old.period, &bucket);
+                old_amount = old.amount;
+            }
+            let open = self.open_positions.get(account).unwrap_or(0);
+            if old_amount == 0 && amount > 0 {
+                self.open_positions.insert(account, &(open + 1));
+                if open == 0 {
+                    self._add_staker(account);
+                }
+            } else if old_amount > 0 && amount == 0 {
+                self.open_positions.insert(account, &(open - 1));
+                if open == 1 {
+                    self._remove_staker(account);
+                }
+            }
+            self.staked_by_period.insert(periods, &(self.staked_in_period(periods) + amount));
+            self.stakes.insert((account, position), &StakeInfo {
+                amount,
+                started_at,
+                period: periods,
+                active_until: until,
+            });
+            Ok(())
+        }
+    }
+
+    #[ink(event)]
+    pub struct Stake {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub staked_at: u64,
+        pub period: u32,
+        pub sum: u128,
+        pub total_staked: u128,
+    }
+
+    #[ink(event)]
+    pub struct Extended {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub old_until: u64,
+        pub new_until: u64,
+        pub period: u32,
+    }
+
+    #[ink(event)]
+    pub struct Withdraw {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub sum: u128,
+        /// Set by `emergency_withdraw` before `active_until`, the only exit that pays a fee.
+        pub is_early: bool,
+    }
+
+    #[ink(event)]
+    pub struct RewardPoolUpdated {
+        #[ink(topic)]
+        pub funder: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        pub previous: AccountId,
+        #[ink(topic)]
+        pub new: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OperatorAdded {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OperatorRemoved {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    /// Emitted by every numeric owner setter, `key` is a `Param` discriminant.
+    #[ink(event)]
+    pub struct ParamChanged {
+        #[ink(topic)]
+        pub key: u8,
+        pub old: u128,
+        pub new: u128,
+    }
+
+    #[ink(event)]
+    pub struct RateChangeScheduled {
+        pub change: RateChange,
+        pub effective_at: u64,
+    }
+
+    /// A due change `apply_pending_rate` skipped because it would exceed `max_reward_rate`.
+    #[ink(event)]
+    pub struct RateChangeDropped {
+        pub change: RateChange,
+    }
+
+    #[ink(event)]
+    pub struct RewardRateChanged {
+        pub old: u128,
+        pub new: u128,
+    }
+
+    #[ink(event)]
+    pub struct EarlyWithdrawFeeChanged {
+        pub old: u128,
+        pub new: u128,
+    }
+
+    #[ink(event)]
+    pub struct ReferralClaimed {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardTokensFunded {
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardSurplusWithdrawn {
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardTokenChanged {
+        pub old: AccountId,
+        pub new: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ConversionRateChanged {
+        pub old: u128,
+        pub new: u128,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct WinddownStarted {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RewardsPaused {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RewardsResumed {
+        #[ink(topic)]
+        pub account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct FeeCollected {
+        #[ink(topic)]
+        pub from: AccountId,
+        pub amount: u128,
+        #[ink(topic)]
+        pub recipient: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawRequested {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub position: u32,
+        pub ready_at: u64,
+    }
+
+    #[ink(event)]
+    pub struct ForceUnlocked {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub old_until: u64,
+    }
+
+    #[ink(event)]
+    pub struct RewardsForfeited {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardsReclaimed {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Slashed {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Compounded {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct ClaimSkipped {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub owed: u128,
+        pub available: u128,
+    }
+
+    #[ink(event)]
+    pub struct Claim {
+        #[ink(topic)]
+        pub account: AccountId,
+        pub periods: u32,
+        pub amount: u128,
+    }
+}
+
+/// In-memory token contracts for the unit tests, see `TokenLedger`.
+#[cfg(test)]
+mod mock {
+    use crate::staking::{StakingError, TokenLedger};
+    use ink::primitives::AccountId;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    thread_local! {
+        /// `(token, owner)` balances. Payers of `transfer_from` are not debited, so test
+        /// accounts stake and fund without being minted first.
+        static BALANCES: RefCell<BTreeMap<(AccountId, AccountId), u128>> = const { RefCell::new(BTreeMap::new()) };
+        /// Accounts the mocked booster collection reports a token for.
+        static BOOSTER_HOLDERS: RefCell<Vec<AccountId>> = const { RefCell::new(Vec::new()) };
+        /// `(owner, signature)` pairs the mocked staking token accepts as permits.
+        static PERMITS: RefCell<Vec<(AccountId, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub fn set_balance(token: AccountId, owner: AccountId, balance: u128) {
+        BALANCES.with(|balances| balances.borrow_mut().insert((token, owner), balance));
+    }
+
+    pub fn balance(token: AccountId, owner: AccountId) -> u128 {
+        BALANCES.with(|balances| balances.borrow().get(&(token, owner)).copied().unwrap_or(0))
+    }
+
+    pub fn add_booster_holder(account: AccountId) {
+        BOOSTER_HOLDERS.with(|holders| holders.borrow_mut().push(account));
+    }
+
+    pub fn add_permit(owner: AccountId, signature: Vec<u8>) {
+        PERMITS.with(|permits| permits.borrow_mut().push((owner, signature)));
+    }
+
+    pub struct MockLedger;
+
+    impl TokenLedger for MockLedger {
+        fn transfer(token: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            let contract = ink::env::account_id::<ink::env::DefaultEnvironment>();
+            let left = balance(token, contract).checked_sub(amount).ok_or(StakingError::TransferFailed)?;
+            set_balance(token, contract, left);
+            set_balance(token, to, balance(token, to) + amount);
+            Ok(())
+        }
+
+        fn transfer_from(token: AccountId, _from: AccountId, to: AccountId, amount: u128) -> Result<(), StakingError> {
+            set_balance(token, to, balance(token, to) + amount);
+            Ok(())
+        }
+
+        fn balance_of(token: AccountId, owner: AccountId) -> u128 {
+            balance(token, owner)
+        }
+
+        fn permit(_token: AccountId, owner: AccountId, _spender: AccountId, _amount: u128, _deadline: u64, signature: Vec<u8>) -> Result<(), StakingError> {
+            if PERMITS.with(|permits| permits.borrow().contains(&(owner, signature))) {
+                return Ok(());
+            }
+            Err(StakingError::PermitFailed)
+        }
+
+        fn holds_booster(_collection: AccountId, account: AccountId) -> bool {
+            BOOSTER_HOLDERS.with(|holders| holders.borrow().contains(&account))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock;
+    use crate::staking::{Param, RateChange, StakeEvent, StakeInfo, Staking, StakingError};
+    use ink::env::{test, DefaultEnvironment};
+    use log::info;
+    use token::usdt_psp22::USDT;
+    use openbrush::contracts::traits::psp22::PSP22;
+
+    // Initialize the logger once for all tests in this module
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    type Event = <Staking as ink::reflect::ContractEventBase>::Type;
+
+    fn last_event() -> Event {
+        let event = test::recorded_events().last().expect("no event emitted");
+        <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data")
+    }
+
+    fn recorded_events() -> Vec<Event> {
+        test::recorded_events()
+            .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("invalid event data"))
+            .collect()
+    }
+
+    /// Tops up the budget and the reward tokens that back it.
+    fn fund_rewards(staking: &mut Staking, amount: u128) {
+        test::set_value_transferred::<DefaultEnvironment>(amount);
+        staking.update_rewards_pool().unwrap();
+        staking.fund_rewards_psp22(staking._reward_token_amount(amount).unwrap()).unwrap();
+    }
+
+    fn get_account_id_from_contract(_contract_address: &dyn PSP22) -> ink::primitives::AccountId {
+        ink::env::account_id::<DefaultEnvironment>()
+    }
+
+    fn create_sp22_token() -> (USDT, ink::primitives::AccountId) {
+        let reward_token = USDT::new(1_000_000);
+        let token_address = get_account_id_from_contract(&reward_token);
+
+        (reward_token, token_address)
+        // Deploy the staking contract with the PSP22 token as the reward token
+    }
+
+    #[ink::test]
     fn test_new() {
         init();
         let accounts = test::default_accounts::<DefaultEnvironment>();
         test::set_caller::<DefaultEnvironment>(accounts.alice);
-        // Deploy the PSP22 token contract
-        let(reward_token, reward_token_account_id) = create_sp22_token();
-        // Deploy the staking contract with the PSP22 token as the reward token
-        let staking = Staking::new(reward_token_account_id, 1);
-        
-        let alice_balance = reward_token.balance_of(accounts.alice);
+        // Deploy the PSP22 token contract
+        let(reward_token, reward_token_account_id) = create_sp22_token();
+        // Deploy the staking contract with the PSP22 token as the reward token
+        let staking = Staking::new(reward_token_account_id, reward_token_account_id, 1, 12, 12, 100, 0);
+        
+        let alice_balance = reward_token.balance_of(accounts.alice);
+
+        info!("alice balance: {}", alice_balance);
+
+        // assert_eq!(staking.reward_token, reward_token_account_id);
+        assert_eq!(staking.staking_token, reward_token_account_id);
+        assert_eq!(staking.reward_conversion_rate, 1);
+        assert_eq!(staking.available_periods, vec![6, 12]);
+    }
+
+
+    #[ink::test]
+    fn test_update_rewards_pool() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_value_transferred::<DefaultEnvironment>(100);
+        staking.update_rewards_pool().unwrap();
+
+        assert_eq!(staking.rewards_balance, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        test::set_value_transferred::<DefaultEnvironment>(50);
+        staking.update_rewards_pool().unwrap();
+        let Event::RewardPoolUpdated(updated) = last_event() else { panic!("expected RewardPoolUpdated event") };
+        assert_eq!(updated.funder, accounts.charlie);
+        assert_eq!(updated.amount, 50);
+        assert_eq!(staking.rewards_balance, 150);
+    }
+
+    #[ink::test]
+    fn test_transfer_ownership() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.owner(), accounts.alice);
+
+        staking.transfer_ownership(accounts.bob).unwrap();
+        assert_eq!(staking.owner(), accounts.bob);
+    }
+
+    #[ink::test]
+    fn test_transfer_ownership_not_owner() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.transfer_ownership(accounts.bob), Err(StakingError::NotOwner));
+        assert_eq!(staking.owner(), accounts.alice);
+    }
+
+    #[ink::test]
+    fn test_operators() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert!(!staking.is_operator(accounts.charlie));
+
+        staking.add_operator(accounts.charlie).unwrap();
+        assert!(staking.is_operator(accounts.charlie));
+
+        staking.remove_operator(accounts.charlie).unwrap();
+        assert!(!staking.is_operator(accounts.charlie));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.add_operator(accounts.bob), Err(StakingError::NotOwner));
+    }
+
+    #[ink::test]
+    fn test_set_reward_rate() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.set_reward_rate(10), Err(StakingError::NotOwner));
+
+        // One reward period passes
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.available_rewards(accounts.bob).unwrap(), 500);
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.set_reward_rate(10).unwrap();
+        assert_eq!(staking.reward_rate(), 10);
+        assert_eq!(staking.available_rewards(accounts.bob).unwrap(), 1000);
+    }
+
+    #[ink::test]
+    fn test_stake() {
+        init();
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake(6, 10, false).unwrap(), 10);
+        assert_eq!(staking.stake(6, 5, false).unwrap(), 15);
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        
+
+        info!("Testing staking with amount: {}, periods: {}", stake_info.amount, stake_info.period);
+
+        assert_eq!(stake_info.amount, 15);
+        assert_eq!(stake_info.period, 6);
+    }
+
+    #[ink::test]
+    fn test_emergency_withdraw() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+
+        // Ensure some time passes
+        test::advance_block::<DefaultEnvironment>();
+
+        // Perform emergency withdraw
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.emergency_withdraw().unwrap();
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.amount, 0);
+    }
+
+
+    #[ink::test]
+    fn test_emergency_withdraw_fee() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        let amount = 100;
+
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, amount, false).unwrap();
+
+        // Exit in the middle of the lock
+        test::advance_block::<DefaultEnvironment>();
+        staking.emergency_withdraw().unwrap();
+
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(staking.early_withdraw_fee, 10);
+        assert_eq!(withdraw.sum, 90);
+        assert!(withdraw.is_early);
+        assert_eq!(staking.rewards_balance, 0);
+    }
+
+    #[ink::test]
+    fn test_extend() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+
+        // Fund the pool and let the lock run out
+        fund_rewards(&mut staking, 100);
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 1);
+
+        // Perform extend
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        let new_until = staking.extend(6).unwrap();
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.period, 6);
+        assert_eq!(stake_info.active_until, new_until);
+    }
+
+    #[ink::test]
+    fn test_withdraw() {
+        init();
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        let amount = 10;
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, amount, false).unwrap();
+
+        // Fund the pool and let the lock run out
+        fund_rewards(&mut staking, 100);
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+
+        // Perform withdraw
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.withdraw().unwrap();
+
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        info!("BOB WITHDREW: {}", withdraw.sum);
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.amount, 0);
+        assert_eq!(withdraw.sum, amount);
+        assert!(!withdraw.is_early);
+    }
+
+    #[ink::test]
+    fn test_withdraw_locked() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+
+        // Only a little time passes, the lock is still running
+        test::advance_block::<DefaultEnvironment>();
+
+        assert_eq!(staking.withdraw(), Err(StakingError::StillLocked));
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.amount, 10);
+    }
+
+    #[ink::test]
+    fn test_claim() {
+        init();
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        // Set up initial stake
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+
+        // Ensure some time passes
+        test::advance_block::<DefaultEnvironment>();
+
+        // Perform claim
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        info!("ACTIVE UNTIL {}", stake_info.active_until);
+
+        // staking.claim().unwrap();
+
+        // let reward = staking.available_rewards(accounts.bob).unwrap();
+        // assert!(reward > 0);
+    }
+
+    #[ink::test]
+    fn test_claim_after_lock_end() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+
+        // Claim everything right at the lock end
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        staking.claim().unwrap();
+        assert_eq!(staking.rewards_balance, 75);
+
+        // Nothing is left to claim afterwards, neither before nor after closing the position
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 86400 * 3);
+        assert_eq!(staking.passed_reward_periods(accounts.bob).unwrap(), 0);
+        assert_eq!(staking.available_rewards(accounts.bob).unwrap(), 0);
+        staking.withdraw().unwrap();
+        assert_eq!(staking.available_rewards(accounts.bob).unwrap(), 0);
+        assert_eq!(staking.rewards_balance, 75);
+    }
+
+    #[ink::test]
+    fn test_withdraw_partial() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+
+        assert_eq!(staking.withdraw_partial(0), Err(StakingError::ZeroAmount));
+        assert_eq!(staking.withdraw_partial(11), Err(StakingError::AmountExceedsStake));
+
+        staking.withdraw_partial(5).unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+
+        let remaining = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(remaining.amount, 5);
+        assert_eq!(remaining.active_until, stake_info.active_until);
+        assert_eq!(staking.total_staked, 5);
+        assert_eq!(withdraw.sum, 5);
+    }
+
+    #[ink::test]
+    fn test_total_staked_after_withdraw() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+        assert_eq!(staking.total_staked(), 10);
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        staking.withdraw().unwrap();
+        assert_eq!(staking.total_staked(), 0);
+    }
+
+    #[ink::test]
+    fn test_pause() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+        assert_eq!(staking.pause(), Err(StakingError::NotOwner));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.pause().unwrap();
+
+        // New stakes are blocked, but exiting still works
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake(6, 10, false), Err(StakingError::Paused));
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.unpause().unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+    }
+
+    #[ink::test]
+    fn test_stake_psp22() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        // Deploy the PSP22 token contract and stake it
+        let (staking_token, staking_token_account_id) = create_sp22_token();
+        let mut staking = Staking::new(staking_token_account_id, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking_token.balance_of(accounts.alice), 1_000_000);
+
+        staking.stake(6, 1000, false).unwrap();
+        let Event::Stake(stake) = last_event() else { panic!("expected Stake event") };
+        assert_eq!(stake.sum, 1000);
+        assert_eq!(staking.total_staked(), 1000);
+
+        let stake_info = staking.stakes.get((accounts.alice, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        fund_rewards(&mut staking, 10_000);
+        staking.withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 1000);
+        assert_eq!(staking.total_staked(), 0);
+    }
+
+    #[ink::test]
+    fn test_extend_event() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10, false).unwrap();
+        let old_until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+        let now = old_until + 1;
+        test::set_block_timestamp::<DefaultEnvironment>(now);
+        staking.extend(12).unwrap();
+
+        let Event::Extended(extended) = last_event() else { panic!("expected Extended event") };
+        assert_eq!(extended.account, accounts.bob);
+        assert_eq!(extended.old_until, old_until);
+        assert_eq!(extended.new_until, now + 12 * 30 * 86400);
+        assert_eq!(extended.new_until, staking.stakes.get((accounts.bob, 0)).unwrap().active_until);
+        assert_eq!(extended.period, 12);
+    }
+
+    #[ink::test]
+    fn test_stake_new_positions() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 100);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake_new(6, 10).unwrap(), 0);
+        assert_eq!(staking.stake_new(12, 20).unwrap(), 1);
+        assert_eq!(staking.position_count(accounts.bob), 2);
+
+        let first = staking.stakes.get((accounts.bob, 0)).unwrap();
+        let second = staking.stakes.get((accounts.bob, 1)).unwrap();
+        assert_eq!((first.amount, first.period), (10, 6));
+        assert_eq!((second.amount, second.period), (20, 12));
+        assert!(first.active_until < second.active_until);
+        assert_eq!(staking.total_staked(), 30);
+
+        // The shorter position unlocks first and closes independently
+        test::set_block_timestamp::<DefaultEnvironment>(first.active_until);
+        assert_eq!(staking.withdraw_position(1), Err(StakingError::StillLocked));
+        staking.withdraw_position(0).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 0);
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 20);
+        staking.claim_position(1).unwrap();
+        assert_eq!(staking.total_staked(), 20);
+    }
+
+    #[ink::test]
+    fn test_period_multiplier() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_period_multiplier(5, 200), Err(StakingError::PeriodNotFound));
+        staking.set_period_multiplier(12, 200).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(12, 36000, false).unwrap();
+        assert_eq!(staking.set_period_multiplier(12, 300), Err(StakingError::NotOwner));
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.available_rewards(accounts.charlie).unwrap(), 500);
+        assert_eq!(staking.available_rewards(accounts.bob).unwrap(), 1000);
+    }
+
+    #[ink::test]
+    fn test_apy_for_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        assert_eq!(staking.apy_for_period(6).unwrap(), 50694);
+        assert_eq!(staking.apy_for_period(5), Err(StakingError::PeriodNotFound));
+
+        staking.set_period_multiplier(12, 200).unwrap();
+        assert_eq!(staking.apy_for_period(12).unwrap(), 101388);
+
+        // Follows the schedule like `daily_reward` does
+        staking.set_rate_schedule(vec![(86400, 10)]).unwrap();
+        assert_eq!(staking.apy_for_period(6).unwrap(), 50694);
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.apy_for_period(6).unwrap(), 101388);
+
+        staking.reward_rate = u128::MAX;
+        staking.rate_schedule = Vec::new();
+        assert_eq!(staking.apy_for_period(6), Err(StakingError::RewardOverflow));
+    }
+
+    #[ink::test]
+    fn test_manage_periods() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        staking.add_period(3).unwrap();
+        assert_eq!(staking.available_periods(), vec![6, 12, 3]);
+        assert_eq!(staking.add_period(3), Err(StakingError::PeriodExists));
+        assert_eq!(staking.remove_period(9), Err(StakingError::PeriodNotFound));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.add_period(24), Err(StakingError::NotOwner));
+        staking.stake(3, 10, false).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().period, 3);
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.remove_period(6).unwrap();
+        assert_eq!(staking.available_periods(), vec![12, 3]);
+    }
+
+    #[ink::test]
+    fn test_claim_errors() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.claim(), Err(StakingError::InsufficientRewards));
+    }
+
+    #[ink::test]
+    fn test_total_rewards_distributed() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 3, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 2);
+        staking.claim().unwrap();
+
+        assert_eq!(staking.total_rewards_distributed(), 1000);
+        assert_eq!(staking.rewards_balance, 10_000 - staking.total_rewards_distributed());
+    }
+
+    #[ink::test]
+    fn test_min_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_min_stake(100).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_min_stake(1), Err(StakingError::NotOwner));
+        assert_eq!(staking.stake(6, 99, false), Err(StakingError::BelowMinStake));
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 100);
+    }
+
+    #[ink::test]
+    fn test_non_reentrant() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        assert_eq!(staking.locked.get(), Some(false));
+
+        // Simulate being called back while a guarded message is still running
+        staking.locked.set(&true);
+        assert_eq!(staking.claim(), Err(StakingError::Reentrant));
+        assert_eq!(staking.withdraw(), Err(StakingError::Reentrant));
+        assert_eq!(staking.emergency_withdraw(), Err(StakingError::Reentrant));
+        assert_eq!(staking.stake(6, 10, false), Err(StakingError::Reentrant));
+    }
+
+    #[ink::test]
+    fn test_rewards_stop_at_active_until() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 1_000_000);
+
+        let start = 1_000;
+        test::set_block_timestamp::<DefaultEnvironment>(start);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        let active_until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+        let lock_days = 6 * 30;
+        assert_eq!(active_until, start + lock_days * 86400);
+
+        // Claim half way, then once more long after the lock expired
+        test::set_block_timestamp::<DefaultEnvironment>(start + 90 * 86400 + 500);
+        assert_eq!(staking.next_reward_date(accounts.bob), Ok(start + 91 * 86400));
+        staking.claim().unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(active_until + 100 * 86400);
+        assert_eq!(staking.next_reward_date(accounts.bob), Ok(active_until));
+        staking.claim().unwrap();
+
+        // 36000 * 5 * 100 / 36000 = 500 per day
+        assert_eq!(staking.total_rewards_distributed(), 500 * lock_days as u128);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        staking.claim().unwrap();
+        assert_eq!(staking.total_rewards_distributed(), 500 * lock_days as u128);
+    }
+
+    #[ink::test]
+    fn test_solvency() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.django, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.solvency(), (0, 0));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        assert_eq!(staking.solvency(), (1000, 1000));
+
+        // Principal leaving the contract outside of a withdrawal shows up as a shortfall
+        mock::set_balance(accounts.django, test::callee::<DefaultEnvironment>(), 400);
+        assert_eq!(staking.solvency(), (400, 1000));
+    }
+
+    #[ink::test]
+    fn test_set_reward_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_reward_period(0), Err(StakingError::InvalidRewardPeriod));
+        staking.set_reward_period(60).unwrap();
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_reward_period(1), Err(StakingError::NotOwner));
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(59);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        test::set_block_timestamp::<DefaultEnvironment>(120);
+        assert_eq!(staking.passed_reward_periods(accounts.bob), Ok(2));
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(1000));
+        assert_eq!(staking.next_reward_date(accounts.bob), Ok(180));
+    }
+
+    #[ink::test]
+    fn test_claim_for_many() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.eve).unwrap();
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+
+        assert_eq!(staking.claim_for_many(vec![accounts.bob]), Err(StakingError::NotOperator));
+        test::set_caller::<DefaultEnvironment>(accounts.eve);
+        assert_eq!(staking.claim_for_many(vec![accounts.bob, accounts.charlie, accounts.django]), Ok(1));
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        assert_eq!(staking.total_rewards_distributed(), 500);
+    }
+
+    #[ink::test]
+    fn test_claim_skipped_on_short_pool() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 600);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        // 100 left in the pool, 500 owed for the second day
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 2);
+        assert_eq!(staking.stake(6, 36000, false), Ok(72000));
+        let skipped = recorded_events()
+            .into_iter()
+            .find_map(|event| match event {
+                Event::ClaimSkipped(skipped) => Some(skipped),
+                _ => None,
+            })
+            .expect("expected ClaimSkipped event");
+        assert_eq!(skipped.account, accounts.bob);
+        assert_eq!(skipped.owed, 500);
+        assert_eq!(skipped.available, 100);
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(86400));
+        assert_eq!(staking.passed_reward_periods(accounts.bob), Ok(1));
+        // The skipped day stays priced at the old principal, only later days use the new one
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(500));
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 3);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(500 + 1000));
+        fund_rewards(&mut staking, 10_000);
+        staking.claim().unwrap();
+        let Event::Claim(claim) = last_event() else { panic!("expected Claim event") };
+        assert_eq!(claim.amount, 1500);
+        assert_eq!(staking.owed_rewards.get((accounts.bob, 0)), None);
+    }
+
+    #[ink::test]
+    fn test_fee_recipient() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.fee_recipient, accounts.alice);
+        staking.set_fee_recipient(accounts.eve).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_fee_recipient(accounts.bob), Err(StakingError::NotOwner));
+        staking.stake(6, 100, false).unwrap();
+        staking.emergency_withdraw().unwrap();
+
+        let fee = recorded_events()
+            .into_iter()
+            .find_map(|event| match event {
+                Event::FeeCollected(fee) => Some(fee),
+                _ => None,
+            })
+            .expect("expected FeeCollected event");
+        assert_eq!(fee.from, accounts.bob);
+        assert_eq!(fee.amount, 10);
+        assert_eq!(fee.recipient, accounts.eve);
+    }
+
+    #[ink::test]
+    fn test_early_withdraw_preview() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.early_withdraw_preview(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.early_withdraw_preview(accounts.bob), Ok((10, 90)));
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        assert_eq!(staking.early_withdraw_preview(accounts.bob), Ok((0, 100)));
+        staking.emergency_withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 100);
+    }
+
+    #[ink::test]
+    fn test_stakers_paged() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        staking.stake(6, 100, false).unwrap();
+        staking.stake_new(12, 100).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob, accounts.charlie]);
+        assert_eq!(staking.stakers_paged(1, 10), vec![accounts.charlie]);
+        assert_eq!(staking.stakers_paged(0, 1), vec![accounts.bob]);
+
+        // Bob stays listed while his second position is open
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob, accounts.charlie]);
+        let stake_info = staking.stakes.get((accounts.bob, 1)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        staking.withdraw_position(1).unwrap();
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.charlie]);
+    }
+
+    #[ink::test]
+    fn test_set_reward_conversion_rate() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_reward_conversion_rate(0), Err(StakingError::InvalidConversionRate));
+        staking.set_reward_conversion_rate(3).unwrap();
+        let Event::ConversionRateChanged(changed) = last_event() else { panic!("expected ConversionRateChanged event") };
+        assert_eq!((changed.old, changed.new), (1, 3));
+        assert_eq!(staking.reward_conversion_rate(), 3);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_reward_conversion_rate(2), Err(StakingError::NotOwner));
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+
+        // The pool is charged the same reward, only the token payout is scaled
+        let Event::Claim(claim) = last_event() else { panic!("expected Claim event") };
+        assert_eq!(claim.amount, 500);
+        assert_eq!(staking.rewards_balance, 9_500);
+        assert_eq!(staking._reward_token_amount(claim.amount), Ok(1500));
+    }
+
+    #[ink::test]
+    fn test_compound() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.compound(), Err(StakingError::TooEarly));
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 2 + 10);
+        staking.compound().unwrap();
+        let Event::Compounded(compounded) = last_event() else { panic!("expected Compounded event") };
+        assert_eq!(compounded.amount, 1000);
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 37000);
+        assert_eq!(staking.total_staked(), 37000);
+        assert_eq!(staking.rewards_balance, 9_000);
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(86400 * 2));
+        assert_eq!(staking.passed_reward_periods(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_all_stake_info() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.all_stake_info(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 3 + 5);
+
+        let view = staking.all_stake_info(accounts.bob).unwrap();
+        assert_eq!(view.amount, 36000);
+        assert_eq!(view.started_at, 0);
+        assert_eq!(view.period, 6);
+        assert_eq!(view.active_until, 6 * 30 * 86400);
+        assert_eq!(view.pending_rewards, 1500);
+        assert_eq!(view.next_reward_at, 86400 * 4);
+    }
+
+    #[ink::test]
+    fn test_slash() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.eve).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        assert_eq!(staking.slash(accounts.bob, 5000, 0), Err(StakingError::NotOperator));
+
+        test::set_caller::<DefaultEnvironment>(accounts.eve);
+        assert_eq!(staking.slash(accounts.bob, 10001, 0), Err(StakingError::InvalidBps));
+        assert_eq!(staking.slash(accounts.bob, 5000, 0), Ok(500));
+        let Event::Slashed(slashed) = last_event() else { panic!("expected Slashed event") };
+        assert_eq!(slashed.account, accounts.bob);
+        assert_eq!(slashed.amount, 500);
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 500);
+        assert_eq!(staking.total_staked(), 500);
+
+        assert_eq!(staking.slash(accounts.bob, 10000, 1), Ok(500));
+        assert!(staking.stakers_paged(0, 10).is_empty());
+    }
+
+    #[ink::test]
+    fn test_next_reward_date_after_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_block_timestamp::<DefaultEnvironment>(1_000);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(1_000));
+        assert_eq!(staking.next_reward_date(accounts.bob), Ok(1_000 + 86400));
+    }
+
+    #[ink::test]
+    fn test_max_total_staked() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_max_total_staked(1000).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_max_total_staked(0), Err(StakingError::NotOwner));
+        staking.stake(6, 600, false).unwrap();
+        assert_eq!(staking.stake(6, 401, false), Err(StakingError::CapExceeded));
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.stake(6, 401, false), Err(StakingError::CapExceeded));
+        staking.stake(6, 400, false).unwrap();
+        assert_eq!(staking.total_staked(), 1000);
+    }
+
+    #[ink::test]
+    fn test_remaining_reward_periods() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.remaining_reward_periods(accounts.bob), Ok(180));
+        test::set_block_timestamp::<DefaultEnvironment>(86400 + 1);
+        assert_eq!(staking.remaining_reward_periods(accounts.bob), Ok(178));
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 86400);
+        assert_eq!(staking.remaining_reward_periods(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_reclaim_expired() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.eve).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.reclaim_expired(accounts.bob), Err(StakingError::NotOperator));
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.eve);
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + staking.grace_secs);
+        assert_eq!(staking.reclaim_expired(accounts.bob), Err(StakingError::GracePeriodNotOver));
+
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + staking.grace_secs + 1);
+        assert_eq!(staking.reclaim_expired(accounts.bob), Ok(500 * 180));
+        let Event::RewardsReclaimed(reclaimed) = last_event() else { panic!("expected RewardsReclaimed event") };
+        assert_eq!(reclaimed.amount, 500 * 180);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        assert_eq!(staking.reclaim_expired(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_stake_merge_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        staking.stakes.insert((accounts.bob, 0), &StakeInfo {
+            amount: u128::MAX - 10,
+            started_at: 0,
+            period: 6,
+            active_until: 0,
+        });
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake(6, 100, false), Err(StakingError::Overflow));
+
+        staking.total_staked = u128::MAX;
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.stake(6, 1, false), Err(StakingError::Overflow));
+    }
+
+    #[ink::test]
+    fn test_staked_of() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.staked_of(accounts.bob), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 250, false).unwrap();
+        assert_eq!(staking.staked_of(accounts.bob), 250);
+        assert_eq!(staking.staked_of(accounts.charlie), 0);
+    }
+
+    #[ink::test]
+    fn test_claim_cooldown() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_claim_cooldown(2 * 86400).unwrap();
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_claim_cooldown(0), Err(StakingError::NotOwner));
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.claim(), Err(StakingError::Cooldown));
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        staking.claim().unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(3 * 86400);
+        assert_eq!(staking.claim(), Err(StakingError::Cooldown));
+
+        // Topping up still collects the pending day
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.total_rewards_distributed(), 1500);
+        test::set_block_timestamp::<DefaultEnvironment>(5 * 86400);
+        staking.claim().unwrap();
+    }
+
+    #[ink::test]
+    fn test_stake_for() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        assert_eq!(staking.stake_for(accounts.bob, 6, 300), Ok(1));
+        let Event::Stake(stake) = last_event() else { panic!("expected Stake event") };
+        assert_eq!(stake.account, accounts.bob);
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 300);
+        assert!(staking.stakes.get((accounts.bob, 0)).is_none());
+        assert_eq!(staking.staked_of(accounts.alice), 0);
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob]);
+    }
+
+    #[ink::test]
+    fn test_stake_for_zero() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.stake_for(accounts.bob, 6, 0), Err(StakingError::ZeroAmount));
+    }
+
+    #[ink::test]
+    fn test_stake_for_cannot_relock_expired_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        let until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+        test::set_block_timestamp::<DefaultEnvironment>(until);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.stake_for(accounts.bob, 12, 1), Ok(1));
+        let info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!((info.amount, info.period, info.active_until), (36000, 6, until));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!((withdraw.sum, withdraw.is_early), (36000, false));
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 1);
+    }
+
+    #[ink::test]
+    fn test_level_periods() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert!(staking.level_periods(2).is_empty());
+
+        assert_eq!(staking.set_level_periods(2, vec![6, 7]), Err(StakingError::PeriodNotFound));
+        staking.set_level_periods(1, vec![6]).unwrap();
+        staking.set_level_periods(2, vec![6, 12]).unwrap();
+        assert_eq!(staking.level_periods(1), vec![6]);
+        assert_eq!(staking.level_periods(2), vec![6, 12]);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_level_periods(1, vec![12]), Err(StakingError::NotOwner));
+    }
+
+    #[ink::test]
+    fn test_stats() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.stats(), (0, 0, 5, 0));
+
+        fund_rewards(&mut staking, 10_000);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        assert_eq!(staking.stats(), (36000, 9_500, 5, 500));
+    }
+
+    #[ink::test]
+    fn test_force_withdraw() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.force_withdraw(), Err(StakingError::StillLocked));
+
+        // The pool is empty, so the normal path can't settle the rewards
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        assert_eq!(staking.withdraw(), Err(StakingError::InsufficientRewards));
+        staking.force_withdraw().unwrap();
+
+        let events = recorded_events();
+        let Some(Event::RewardsForfeited(forfeited)) = events.iter().rev().nth(1) else { panic!("expected RewardsForfeited event") };
+        assert_eq!(forfeited.amount, 500 * 180);
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 36000);
+        assert!(!withdraw.is_early);
+        assert_eq!(staking.staked_of(accounts.bob), 0);
+        assert_eq!(staking.total_rewards_distributed(), 0);
+    }
+
+    #[ink::test]
+    fn test_reward_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 10u128.pow(30), false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, u128::MAX / 1000, false).unwrap();
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        // 10^30 * 5 * 100 * 180 / 36000
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(25 * 10u128.pow(29)));
+        assert_eq!(staking.available_rewards(accounts.charlie), Err(StakingError::RewardOverflow));
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.claim(), Err(StakingError::RewardOverflow));
+    }
+
+    #[ink::test]
+    fn test_set_early_withdraw_fee() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.early_withdraw_fee(), 10);
+        assert_eq!(staking.set_early_withdraw_fee(101), Err(StakingError::InvalidFee));
+        staking.set_early_withdraw_fee(25).unwrap();
+        let Event::EarlyWithdrawFeeChanged(changed) = last_event() else { panic!("expected EarlyWithdrawFeeChanged event") };
+        assert_eq!((changed.old, changed.new), (10, 25));
+        assert_eq!(staking.early_withdraw_fee(), 25);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_early_withdraw_fee(0), Err(StakingError::NotOwner));
+        staking.stake(6, 100, false).unwrap();
+        staking.emergency_withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 75);
+    }
+
+    #[ink::test]
+    fn test_stake_reset_lock() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        let first_until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+
+        // Merging averages the 170 days left and the new 180 days
+        test::set_block_timestamp::<DefaultEnvironment>(10 * 86400);
+        staking.stake(6, 100, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.amount, 200);
+        assert_eq!(stake_info.active_until, first_until + 5 * 86400);
+
+        // Resetting locks the whole position again from now
+        staking.stake(12, 100, true).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.amount, 300);
+        assert_eq!(stake_info.period, 12);
+        assert_eq!(stake_info.active_until, 10 * 86400 + 12 * 30 * 86400);
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(0));
+    }
+
+    #[ink::test]
+    fn test_is_locked() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.is_locked(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.is_locked(accounts.bob), Ok(true));
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        assert_eq!(staking.is_locked(accounts.bob), Ok(false));
+    }
+
+    #[ink::test]
+    fn test_pause_rewards() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.pause_rewards(), Err(StakingError::NotOwner));
+
+        // Half a day accrues before the pause, then ten days are paused
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_block_timestamp::<DefaultEnvironment>(86400 / 2);
+        staking.pause_rewards().unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 10);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        // Staking still works and a stake opened during the pause starts at the resume
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 20 - 86400 / 2);
+        staking.resume_rewards().unwrap();
+        let Event::RewardsResumed(_) = last_event() else { panic!("expected RewardsResumed event") };
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 20);
+        assert_eq!(staking.passed_reward_periods(accounts.bob), Ok(1));
+        assert_eq!(staking.available_rewards(accounts.charlie), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_pause_keeps_earned_rewards() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400 + 86400 / 2);
+        staking.pause_rewards().unwrap();
+
+        // Two full periods were earned before the pause, none after it
+        test::set_block_timestamp::<DefaultEnvironment>(10 * 86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(1000));
+        assert_eq!(staking.all_stake_info(accounts.bob).unwrap().pending_rewards, 1000);
+
+        staking.force_unlock(accounts.bob, 0).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.withdraw().unwrap();
+        assert!(recorded_events().iter().any(|event| matches!(event, Event::Claim(claim) if claim.amount == 1000)));
+        assert_eq!(staking.total_rewards_distributed(), 1000);
+    }
+
+    #[ink::test]
+    fn test_set_reward_token() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.bob, 1, 12, 12, 100, 0);
+        assert_eq!(staking.reward_token(), accounts.bob);
+
+        staking.set_reward_token(accounts.charlie).unwrap();
+        let Event::RewardTokenChanged(changed) = last_event() else { panic!("expected RewardTokenChanged event") };
+        assert_eq!((changed.old, changed.new), (accounts.bob, accounts.charlie));
+        assert_eq!(staking.reward_token(), accounts.charlie);
+
+        test::set_value_transferred::<DefaultEnvironment>(100);
+        staking.update_rewards_pool().unwrap();
+        assert_eq!(staking.set_reward_token(accounts.django), Err(StakingError::RewardPoolNotEmpty));
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_reward_token(accounts.django), Err(StakingError::NotOwner));
+        assert_eq!(staking.reward_token(), accounts.charlie);
+    }
+
+    #[ink::test]
+    fn test_withdraw_rewards_surplus() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 1000);
+        assert_eq!(staking.withdraw_rewards_surplus(1001), Err(StakingError::InsufficientRewards));
+        staking.withdraw_rewards_surplus(400).unwrap();
+        let Event::RewardSurplusWithdrawn(withdrawn) = last_event() else { panic!("expected RewardSurplusWithdrawn event") };
+        assert_eq!(withdrawn.amount, 400);
+        assert_eq!(staking.rewards_balance, 600);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.withdraw_rewards_surplus(1), Err(StakingError::NotOwner));
+    }
+
+    #[ink::test]
+    fn test_referral_bonus() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_referral_bonus(10001), Err(StakingError::InvalidBps));
+        staking.set_referral_bonus(1000).unwrap();
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake_with_referrer(6, 36000, accounts.bob), Err(StakingError::SelfReferral));
+        staking.stake_with_referrer(6, 36000, accounts.charlie).unwrap();
+        assert_eq!(staking.referrers.get(accounts.bob), Some(accounts.charlie));
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        assert_eq!(staking.referral_earnings(accounts.charlie), 50);
+        assert_eq!(staking.rewards_balance, 10_000 - 500 - 50);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.claim_referral(), Ok(50));
+        let Event::ReferralClaimed(claimed) = last_event() else { panic!("expected ReferralClaimed event") };
+        assert_eq!(claimed.amount, 50);
+        assert_eq!(staking.referral_earnings(accounts.charlie), 0);
+        assert_eq!(staking.claim_referral(), Err(StakingError::ZeroAmount));
+    }
+
+    #[ink::test]
+    fn test_get_staking_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.get_staking_period(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(stake_info.period));
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(6));
+
+        // A closed position reports 0 instead of underflowing
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_history_of() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert!(staking.history_of(accounts.bob).is_empty());
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        assert_eq!(staking.history_of(accounts.bob), vec![
+            StakeEvent::Staked { timestamp: 0, amount: 36000 },
+            StakeEvent::Claimed { timestamp: 86400, amount: 500 },
+        ]);
+
+        // Only the newest entries are kept
+        for _ in 0..60 {
+            staking.stake(6, 1, false).unwrap();
+        }
+        let history = staking.history_of(accounts.bob);
+        assert_eq!(history.len(), 50);
+        assert_eq!(history[0], StakeEvent::Staked { timestamp: 86400, amount: 1 });
+    }
+
+    #[ink::test]
+    fn test_projected_reward_at() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.projected_reward_at(accounts.bob, 999), Err(StakingError::TimestampInPast));
+        assert_eq!(staking.projected_reward_at(accounts.bob, 1000 + 86400), Ok(500));
+        assert_eq!(staking.projected_reward_at(accounts.bob, u64::MAX), Ok(500 * 180));
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_emergency_withdraw_zeroes_position() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        let events_before = test::recorded_events().count();
+        staking.emergency_withdraw().unwrap();
+
+        assert_eq!(staking.stakes.get((accounts.bob, 0)), Some(StakeInfo {
+            amount: 0,
+            started_at: 0,
+            period: 0,
+            active_until: 0,
+        }));
+        assert_eq!(staking.total_staked(), 0);
+        let withdrawals = recorded_events()
+            .into_iter()
+            .skip(events_before)
+            .filter(|event| matches!(event, Event::Withdraw(_)))
+            .count();
+        assert_eq!(withdrawals, 1);
+    }
+
+
+    #[ink::test]
+    fn test_reward_token_decimals() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        // USDT-like reward token with 6 decimals against a 12 decimal pool
+        let usdt = Staking::new(accounts.alice, accounts.bob, 2, 6, 12, 100, 0);
+        assert_eq!(usdt._reward_token_amount(1_000_000_000_000), Ok(2_000_000));
+        let wide = Staking::new(accounts.alice, accounts.bob, 2, 18, 12, 100, 0);
+        assert_eq!(wide._reward_token_amount(1_000_000_000_000), Ok(2_000_000_000_000_000_000));
+        let same = Staking::new(accounts.alice, accounts.bob, 2, 12, 12, 100, 0);
+        assert_eq!(same._reward_token_amount(500), Ok(1000));
+    }
+    #[ink::test]
+    fn test_staked_in_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(12, 300, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.django);
+        staking.stake(6, 50, false).unwrap();
+        assert_eq!(staking.staked_in_period(6), 150);
+        assert_eq!(staking.staked_in_period(12), 300);
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        assert_eq!(staking.remove_period(6), Err(StakingError::PeriodInUse));
+
+        // Extending moves the whole position into the new bucket
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 1);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.extend(12).unwrap();
+        assert_eq!(staking.staked_in_period(6), 50);
+        assert_eq!(staking.staked_in_period(12), 400);
+
+        test::set_caller::<DefaultEnvironment>(accounts.django);
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.staked_in_period(6), 0);
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.remove_period(6).unwrap();
+    }
+
+    #[ink::test]
+    fn test_force_unlock() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        fund_rewards(&mut staking, 10_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.force_unlock(accounts.bob, 0), Err(StakingError::NotOwner));
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.withdraw(), Err(StakingError::StillLocked));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.force_unlock(accounts.bob, 0).unwrap();
+        let Event::ForceUnlocked(unlocked) = last_event() else { panic!("expected ForceUnlocked event") };
+        assert_eq!(unlocked.account, accounts.bob);
+        assert_eq!(unlocked.old_until, 6 * 30 * 86400);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 100);
+        assert!(!withdraw.is_early);
+    }
+
+    #[ink::test]
+    fn test_seconds_until_unlock() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.seconds_until_unlock(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.seconds_until_unlock(accounts.bob), Ok(6 * 30 * 86400));
+        test::set_block_timestamp::<DefaultEnvironment>(100);
+        assert_eq!(staking.seconds_until_unlock(accounts.bob), Ok(6 * 30 * 86400 - 100));
+        test::set_block_timestamp::<DefaultEnvironment>(7 * 30 * 86400);
+        assert_eq!(staking.seconds_until_unlock(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_fund_rewards_psp22() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        let (reward_token, reward_token_account_id) = create_sp22_token();
+        let mut staking = Staking::new(accounts.alice, reward_token_account_id, 2, 12, 12, 100, 0);
+        assert_eq!(reward_token.balance_of(accounts.alice), 1_000_000);
+
+        assert_eq!(staking.fund_rewards_psp22(0), Err(StakingError::ZeroAmount));
+        staking.fund_rewards_psp22(1000).unwrap();
+        let Event::RewardTokensFunded(funded) = last_event() else { panic!("expected RewardTokensFunded event") };
+        assert_eq!(funded.amount, 1000);
+        assert_eq!(staking.reward_token_balance, 1000);
+
+        // A day of rewards is 500 in the budget and 1000 reward tokens
+        test::set_value_transferred::<DefaultEnvironment>(10_000);
+        staking.update_rewards_pool().unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 2);
+        assert_eq!(staking.claim(), Err(StakingError::InsufficientRewards));
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.claim().unwrap();
+        assert_eq!(staking.reward_token_balance, 0);
+        assert_eq!(staking.rewards_balance, 9_500);
+    }
+
+    #[ink::test]
+    fn test_stake_info_many() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 100, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(12, 200, false).unwrap();
+
+        let infos = staking.stake_info_many(vec![accounts.bob, accounts.django, accounts.charlie]);
+        assert_eq!(infos, vec![
+            (accounts.bob, 100, 0, 6, 6 * 30 * 86400),
+            (accounts.charlie, 200, 0, 12, 12 * 30 * 86400),
+        ]);
+        assert_eq!(staking.stake_info_many(vec![accounts.django; 101]).len(), 0);
+        assert_eq!(staking.stake_info_many(vec![accounts.bob; 150]).len(), 100);
+    }
+
+    #[ink::test]
+    fn test_extend_invalid_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 1);
+
+        assert_eq!(staking.extend(5), Err(StakingError::PeriodNotFound));
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(0));
+        assert_eq!(staking.rewards_balance, 1_000_000);
+    }
+
+    #[ink::test]
+    fn test_rate_schedule() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.set_rate_schedule(vec![(10, 1), (10, 2)]), Err(StakingError::InvalidSchedule));
+        staking.set_rate_schedule(vec![(10 * 86400, 10), (15 * 86400, 2)]).unwrap();
+        assert_eq!(staking.rate_schedule(), vec![(10 * 86400, 10), (15 * 86400, 2)]);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_rate_schedule(Vec::new()), Err(StakingError::NotOwner));
+        staking.stake(6, 36000, false).unwrap();
+
+        // 10 days at rate 5, 5 days at 10 and 5 days at 2
+        test::set_block_timestamp::<DefaultEnvironment>(20 * 86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(10 * 500 + 5 * 1000 + 5 * 200));
+        staking.claim().unwrap();
+
+        // Only the last segment applies afterwards
+        test::set_block_timestamp::<DefaultEnvironment>(22 * 86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(2 * 200));
+    }
+
+
+    #[ink::test]
+    fn test_try_new_rejects_zero_conversion_rate() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        assert_eq!(
+            Staking::try_new(accounts.alice, accounts.alice, 0, 12, 12, 100, 0).err(),
+            Some(StakingError::InvalidConversionRate)
+        );
+        let staking = Staking::try_new(accounts.alice, accounts.alice, 3, 12, 12, 100, 0).ok().unwrap();
+        assert_eq!(staking.reward_conversion_rate(), 3);
+    }
+
+    #[ink::test]
+    #[should_panic(expected = "zero reward conversion rate")]
+    fn test_new_panics_on_zero_conversion_rate() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        Staking::new(accounts.alice, accounts.alice, 0, 12, 12, 100, 0);
+    }
+
+    #[ink::test]
+    fn test_daily_reward() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.daily_reward(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        let daily = staking.daily_reward(accounts.bob).unwrap();
+        assert_eq!(daily, 500);
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.daily_reward(accounts.bob), Ok(daily));
+        staking.claim().unwrap();
+        assert_eq!(staking.total_rewards_distributed(), daily);
+
+        test::set_block_timestamp::<DefaultEnvironment>(6 * 30 * 86400);
+        assert_eq!(staking.daily_reward(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_stake_info_layout() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        let month = 30 * 86400;
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        test::set_block_timestamp::<DefaultEnvironment>(1_700_000_000_000);
+        staking.stake(6, 36000, false).unwrap();
+        let info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(info.active_until, info.started_at + 6 * month);
+        // Entries written by earlier builds still decode
+        let stored = scale::Encode::encode(&(info.amount, info.started_at, info.period, info.active_until));
+        assert_eq!(<StakeInfo as scale::Decode>::decode(&mut &stored[..]), Ok(info.clone()));
+
+        test::set_block_timestamp::<DefaultEnvironment>(info.active_until + 1);
+        staking.extend(12).unwrap();
+        let extended = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(extended.active_until, extended.started_at + 12 * month);
+    }
+
+    #[ink::test]
+    fn test_slash_stale_nonce() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.alice).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        assert_eq!(staking.nonce_of(accounts.bob), 0);
+
+        staking.force_unlock(accounts.bob, 0).unwrap();
+        assert_eq!(staking.nonce_of(accounts.bob), 1);
+        // Built against the state before the unlock
+        assert_eq!(staking.slash(accounts.bob, 5000, 0), Err(StakingError::StaleNonce));
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, 1000);
+
+        assert_eq!(staking.slash(accounts.bob, 5000, 1), Ok(500));
+        assert_eq!(staking.nonce_of(accounts.bob), 2);
+        assert_eq!(staking.nonce_of(accounts.charlie), 0);
+
+        // Every other operator action against the account moves it as well
+        staking.set_level(accounts.bob, 1).unwrap();
+        assert_eq!(staking.nonce_of(accounts.bob), 3);
+        assert_eq!(staking.slash(accounts.bob, 5000, 2), Err(StakingError::StaleNonce));
+        fund_rewards(&mut staking, 1_000_000);
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        // Bob's unlocked position has nothing to pay, only the paid account moves
+        assert_eq!(staking.claim_for_many(vec![accounts.bob, accounts.charlie]), Ok(1));
+        assert_eq!(staking.nonce_of(accounts.bob), 3);
+        assert_eq!(staking.nonce_of(accounts.charlie), 1);
+    }
+
+    #[ink::test]
+    fn test_pool_runway_days() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.pool_runway_days(), u128::MAX);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.pool_runway_days(), 2000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(12, 36000, false).unwrap();
+        assert_eq!(staking.pool_runway_days(), 1000);
+    }
+
+    #[ink::test]
+    fn test_migrate_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        let info = StakeInfo { amount: 36000, started_at: 0, period: 6, active_until: 6 * 30 * 86400 };
+
+        assert_eq!(
+            staking.migrate_stake(accounts.bob, StakeInfo { period: 7, ..info.clone() }, 0),
+            Err(StakingError::PeriodNotFound)
+        );
+        staking.migrate_stake(accounts.bob, info.clone(), 2 * 86400).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 0)), Some(info.clone()));
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(2 * 86400));
+        assert_eq!(staking.total_staked(), 36000);
+        assert_eq!(staking.staked_in_period(6), 36000);
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob]);
+
+        // Re-importing replaces the position instead of adding to it
+        staking.migrate_stake(accounts.bob, info.clone(), 2 * 86400).unwrap();
+        assert_eq!(staking.total_staked(), 36000);
+
+        test::set_block_timestamp::<DefaultEnvironment>(5 * 86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(3 * 500));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.migrate_stake(accounts.bob, info.clone(), 0), Err(StakingError::NotOwner));
+        assert_eq!(staking.finalize_migration(), Err(StakingError::NotOwner));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.finalize_migration().unwrap();
+        assert_eq!(staking.migrate_stake(accounts.charlie, info, 0), Err(StakingError::MigrationClosed));
+    }
+
+    #[ink::test]
+    fn test_max_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_period(24).unwrap();
+        staking.set_max_period(12).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_max_period(0), Err(StakingError::NotOwner));
+        assert_eq!(staking.stake(24, 1000, false), Err(StakingError::PeriodTooLong));
+        staking.stake(12, 1000, false).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.remove_period(24).unwrap();
+        assert_eq!(staking.available_periods(), vec![6, 12]);
+    }
+
+    #[ink::test]
+    fn test_claim_zero_periods_emits_empty_claim() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        staking.claim().unwrap();
+        let Event::Claim(claim) = last_event() else { panic!("expected Claim event") };
+        assert_eq!(claim.account, accounts.bob);
+        assert_eq!(claim.periods, 0);
+        assert_eq!(claim.amount, 0);
+        assert_eq!(staking.last_reward_claims.get((accounts.bob, 0)), Some(0));
+        assert_eq!(staking.history_of(accounts.bob).len(), 1);
+    }
+
+    #[ink::test]
+    fn test_level_gates_periods() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.eve).unwrap();
+        staking.set_level_periods(0, vec![6]).unwrap();
+        staking.set_level_periods(1, vec![6, 12]).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_level(accounts.bob, 1), Err(StakingError::NotOperator));
+        assert_eq!(staking.stake(12, 1000, false), Err(StakingError::PeriodNotAllowed));
+        staking.stake(6, 1000, false).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.eve);
+        staking.set_level(accounts.charlie, 1).unwrap();
+        assert_eq!(staking.level_of(accounts.charlie), 1);
+        assert_eq!(staking.level_of(accounts.bob), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(12, 1000, false).unwrap();
+        assert_eq!(staking.get_staking_period(accounts.charlie), Ok(12));
+    }
+
+    #[ink::test]
+    fn test_withdraw_small_position_immediately() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        staking.set_unbonding(7 * 86400, 10000).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_unbonding(0, 0), Err(StakingError::NotOwner));
+        staking.stake(6, 10000, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        staking.withdraw().unwrap();
+        assert_eq!(staking.staked_of(accounts.bob), 0);
+    }
+
+    #[ink::test]
+    fn test_withdraw_large_position_after_unbonding() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        staking.set_unbonding(7 * 86400, 10000).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.request_withdraw(), Err(StakingError::NoStake));
+        staking.stake(6, 36000, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        assert_eq!(staking.withdraw(), Err(StakingError::WithdrawNotRequested));
+        assert_eq!(staking.emergency_withdraw(), Err(StakingError::WithdrawNotRequested));
+
+        let ready_at = staking.request_withdraw().unwrap();
+        assert_eq!(ready_at, stake_info.active_until + 7 * 86400);
+        let Event::WithdrawRequested(requested) = last_event() else { panic!("expected WithdrawRequested event") };
+        assert_eq!(requested.account, accounts.bob);
+        assert_eq!(requested.ready_at, ready_at);
+
+        test::set_block_timestamp::<DefaultEnvironment>(ready_at - 1);
+        assert_eq!(staking.withdraw(), Err(StakingError::Unbonding));
+        test::set_block_timestamp::<DefaultEnvironment>(ready_at);
+        staking.withdraw().unwrap();
+        assert_eq!(staking.staked_of(accounts.bob), 0);
+        assert_eq!(staking.withdraw_ready_at.get((accounts.bob, 0)), None);
+    }
+
+    #[ink::test]
+    fn test_config() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.config(), (5, 10, 1, vec![6, 12]));
+    }
+
+    #[ink::test]
+    fn test_claim_and_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.claim_and_stake(12), Err(StakingError::TooEarly));
+
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        let pending = staking.available_rewards(accounts.bob).unwrap();
+        assert_eq!(pending, 1000);
+        assert_eq!(staking.claim_and_stake(12), Ok(1));
+
+        let position = staking.stakes.get((accounts.bob, 1)).unwrap();
+        assert_eq!(position.amount, pending);
+        assert_eq!(position.period, 12);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        assert_eq!(staking.total_staked(), 36000 + pending);
+        assert_eq!(staking.rewards_balance, 1_000_000 - pending);
+
+        let events = recorded_events();
+        let [.., Event::Claim(claim), Event::Stake(stake)] = &events[..] else { panic!("expected Claim and Stake events") };
+        assert_eq!(claim.amount, pending);
+        assert_eq!(stake.sum, pending);
+        assert_eq!(stake.period, 12);
+    }
+
+    #[ink::test]
+    fn test_top_up_keeps_longer_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(12, 1000, false).unwrap();
+        let before = staking.stakes.get((accounts.bob, 0)).unwrap();
+
+        test::set_block_timestamp::<DefaultEnvironment>(30 * 86400);
+        staking.stake(6, 1000, false).unwrap();
+        let after = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(12));
+        assert_eq!(after.active_until, before.active_until);
+        assert_eq!(after.amount, 2000);
+        assert_eq!(staking.staked_in_period(12), 2000);
+        assert_eq!(staking.staked_in_period(6), 0);
+    }
+
+    #[ink::test]
+    fn test_booster() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        mock::add_booster_holder(accounts.bob);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_booster(Some(accounts.django), 15000), Err(StakingError::NotOwner));
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(500));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.set_booster(Some(accounts.django), 15000).unwrap();
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(750));
+        assert_eq!(staking.available_rewards(accounts.charlie), Ok(500));
+        assert_eq!(staking.daily_reward(accounts.bob), Ok(750));
+        assert_eq!(staking.daily_reward(accounts.charlie), Ok(500));
+        assert_eq!(staking.apy_for(accounts.bob, 6), Ok(staking.apy_for_period(6).unwrap() * 3 / 2));
+        assert_eq!(staking.apy_for(accounts.charlie, 6), staking.apy_for_period(6));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.claim().unwrap();
+        assert_eq!(staking.total_rewards_distributed(), 750);
+    }
+
+    #[ink::test]
+    fn test_reward_status() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.reward_status(accounts.bob), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400 * 2 + 7);
+
+        let status = staking.reward_status(accounts.bob).unwrap();
+        assert_eq!(status, (1000, 2, 86400 * 3));
+        assert_eq!(status.0, staking.available_rewards(accounts.bob).unwrap());
+        assert_eq!(status.1, staking.passed_reward_periods(accounts.bob).unwrap());
+        assert_eq!(status.2, staking.next_reward_date(accounts.bob).unwrap());
+    }
+
+    #[ink::test]
+    fn test_max_rewards_balance() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_max_rewards_balance(1000).unwrap();
+
+        test::set_value_transferred::<DefaultEnvironment>(600);
+        staking.update_rewards_pool().unwrap();
+        test::set_value_transferred::<DefaultEnvironment>(400);
+        staking.update_rewards_pool().unwrap();
+        assert_eq!(staking.rewards_balance, 1000);
+
+        test::set_value_transferred::<DefaultEnvironment>(1);
+        assert_eq!(staking.update_rewards_pool(), Err(StakingError::PoolCapExceeded));
+        assert_eq!(staking.rewards_balance, 1000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_max_rewards_balance(0), Err(StakingError::NotOwner));
+    }
+
+    #[ink::test]
+    fn test_last_claim_at() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.last_claim_at(accounts.bob), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.last_claim_at(accounts.bob), 1000);
+
+        // The checkpoint moves by whole periods, the remainder keeps accruing
+        test::set_block_timestamp::<DefaultEnvironment>(1000 + 3 * 86400 + 500);
+        staking.claim().unwrap();
+        assert_eq!(staking.last_claim_at(accounts.bob), 1000 + 3 * 86400);
+    }
+
+    #[ink::test]
+    fn test_top_up_weighted_lock() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        let month = 30 * 86400;
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        let old_until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+
+        test::set_block_timestamp::<DefaultEnvironment>(month);
+        staking.stake(12, 9000, false).unwrap();
+        let info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert!(info.active_until > old_until);
+        assert!(info.active_until < month + 12 * month);
+        // (1000 * 5 months + 9000 * 12 months) / 10000
+        assert_eq!(info.active_until, month + (1000 * 5 * month + 9000 * 12 * month) / 10000);
+        assert_eq!(info.period, 12);
+        assert_eq!(info.started_at, 0);
+    }
+
+    #[ink::test]
+    fn test_param_changed() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        staking.set_reward_rate(8).unwrap();
+        let events = recorded_events();
+        let [.., Event::ParamChanged(changed), Event::RewardRateChanged(_)] = &events[..] else { panic!("expected ParamChanged event") };
+        assert_eq!(changed.key, Param::RewardRate as u8);
+        assert_eq!(changed.key, 0);
+        assert_eq!(changed.old, 5);
+        assert_eq!(changed.new, 8);
+
+        staking.set_grace_secs(86400).unwrap();
+        let Event::ParamChanged(changed) = last_event() else { panic!("expected ParamChanged event") };
+        assert_eq!(changed.key, Param::GraceSecs as u8);
+        assert_eq!((changed.old, changed.new), (30 * 86400, 86400));
+
+        staking.set_period_multiplier(12, 200).unwrap();
+        let Event::ParamChanged(changed) = last_event() else { panic!("expected ParamChanged event") };
+        assert_eq!(changed.key, Param::PeriodMultiplier as u8);
+        assert_eq!((changed.old, changed.new), (100, 200));
+
+        staking.set_period_rate(12, 8).unwrap();
+        let Event::ParamChanged(changed) = last_event() else { panic!("expected ParamChanged event") };
+        assert_eq!(changed.key, Param::PeriodRate as u8);
+        assert_eq!((changed.old, changed.new), (0, 8));
+    }
+
+    #[ink::test]
+    fn test_winddown() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(12, 36000, false).unwrap();
+        assert_eq!(staking.begin_winddown(), Err(StakingError::NotOwner));
+        test::set_block_timestamp::<DefaultEnvironment>(10 * 86400);
+        assert_eq!(staking.withdraw(), Err(StakingError::StillLocked));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.begin_winddown().unwrap();
+        let Event::WinddownStarted(started) = last_event() else { panic!("expected WinddownStarted event") };
+        assert_eq!(started.account, accounts.alice);
 
-        info!("alice balance: {}", alice_balance);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.is_locked(accounts.bob), Ok(false));
+        assert_eq!(staking.seconds_until_unlock(accounts.bob), Ok(0));
+        assert_eq!(staking.early_withdraw_preview(accounts.bob), Ok((0, 36000)));
+        assert_eq!(staking.stake(12, 1000, false), Err(StakingError::Winddown));
 
-        // assert_eq!(staking.reward_token, reward_token_account_id);
-        assert_eq!(staking.reward_conversion_rate, 1);
-        assert_eq!(staking.available_periods, vec![6, 12]);
+        staking.withdraw().unwrap();
+        let Event::Withdraw(withdraw) = last_event() else { panic!("expected Withdraw event") };
+        assert_eq!(withdraw.sum, 36000);
+        assert!(!withdraw.is_early);
+        assert_eq!(staking.total_rewards_distributed(), 10 * 500);
+        assert_eq!(staking.staked_of(accounts.bob), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.stake(6, 1000, false), Err(StakingError::Winddown));
     }
 
+    #[ink::test]
+    fn test_min_period() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.set_min_period(12), Err(StakingError::NotOwner));
+
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.set_min_period(12).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        assert_eq!(staking.stake(6, 1000, false), Err(StakingError::PeriodTooShort));
+        staking.stake(12, 1000, false).unwrap();
+
+        // The existing 6-month position may only be extended above the floor
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 1);
+        assert_eq!(staking.extend(6), Err(StakingError::PeriodTooShort));
+        staking.extend(12).unwrap();
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(12));
+    }
 
     #[ink::test]
-    fn test_update_rewards_pool() {
+    fn test_total_pending_rewards() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.total_pending_rewards(), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 72000, false).unwrap();
+
+        test::set_block_timestamp::<DefaultEnvironment>(3 * 86400);
+        // 3 days at 500 plus 2 days at 1000
+        assert_eq!(staking.total_pending_rewards(), 3 * 500 + 2 * 1000);
+    }
+
+    #[ink::test]
+    fn test_withdraw_transfer_failure_is_returned() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+
+        // The off-chain engine doesn't roll storage back, so check the error reaches the
+        // caller: that is what makes the chain revert the zeroed position, which
+        // `e2e_failed_withdraw_keeps_the_position` checks on a node.
+        mock::set_balance(accounts.alice, test::callee::<DefaultEnvironment>(), 35999);
+        assert_eq!(staking.withdraw(), Err(StakingError::TransferFailed));
+    }
+
+    #[ink::test]
+    fn test_period_rate() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.set_period_rate(7, 8), Err(StakingError::PeriodNotFound));
+        staking.set_period_rate(12, 8).unwrap();
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_period_rate(12, 1), Err(StakingError::NotOwner));
+        staking.stake(12, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        // 36000 * 8 * 100 / 36000 per day against the default 5
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(2 * 800));
+        assert_eq!(staking.available_rewards(accounts.charlie), Ok(2 * 500));
+        assert_eq!(staking.daily_reward(accounts.bob), Ok(800));
+    }
+
+    #[ink::test]
+    fn test_extend_preview() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.extend_preview(accounts.bob, 6), Err(StakingError::NoStake));
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.extend_preview(accounts.bob, 12), Err(StakingError::StillActive));
+
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until + 5);
+        assert_eq!(staking.extend_preview(accounts.bob, 7), Err(StakingError::PeriodNotFound));
+        let preview = staking.extend_preview(accounts.bob, 12).unwrap();
+        assert_eq!(staking.extend(12), Ok(preview));
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().active_until, preview);
+    }
+
+    #[ink::test]
+    fn test_stake_with_permit() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        mock::add_permit(accounts.bob, vec![7; 64]);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        assert_eq!(staking.stake_with_permit(6, 1000, 999, vec![7; 64]), Err(StakingError::TimestampInPast));
+        assert_eq!(staking.stake_with_permit(6, 1000, 2000, vec![8; 64]), Err(StakingError::PermitFailed));
+        assert_eq!(staking.stake_with_permit(6, 1000, 2000, vec![7; 64]), Ok(1000));
+        assert_eq!(staking.staked_of(accounts.bob), 1000);
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(6));
+    }
+
+    #[ink::test]
+    fn test_staker_count() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.staker_count(), 0);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        staking.stake(6, 1000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 1000, false).unwrap();
+        assert_eq!(staking.staker_count(), 2);
+
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.staker_count(), 1);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        staking.withdraw().unwrap();
+        assert_eq!(staking.staker_count(), 0);
+    }
+
+    #[ink::test]
+    fn test_round_up_rewards() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.set_round_up_rewards(true), Err(StakingError::NotOwner));
+        staking.stake(6, 10, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        // 10 * 5 * 100 * 2 / 36000 is below one unit
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
 
         test::set_caller::<DefaultEnvironment>(accounts.alice);
-        test::set_value_transferred::<DefaultEnvironment>(100);
-        staking.update_rewards_pool().unwrap();
+        staking.set_round_up_rewards(true).unwrap();
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(1));
+        assert_eq!(staking.daily_reward(accounts.bob), Ok(1));
 
-        assert_eq!(staking.rewards_balance, 100);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.claim().unwrap();
+        assert_eq!(staking.total_rewards_distributed(), 1);
     }
 
     #[ink::test]
-    fn test_stake() {
-        init();
+    fn test_can_claim() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.can_claim(accounts.bob), Err(StakingError::NoStake));
 
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        test::set_value_transferred::<DefaultEnvironment>(10);
-        staking.stake(6).unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.can_claim(accounts.bob), Ok(false));
+        assert_eq!(staking.claim(), Err(StakingError::InsufficientRewards));
 
-        let stake_info = staking.stakes.get(&accounts.bob).unwrap();
-        
+        fund_rewards(&mut staking, 1_000_000);
+        assert_eq!(staking.can_claim(accounts.bob), Ok(true));
+        staking.claim().unwrap();
+        // Nothing left until the next period
+        assert_eq!(staking.can_claim(accounts.bob), Ok(false));
+    }
 
-        info!("Testing staking with amount: {}, periods: {}", stake_info.amount, stake_info.period);
+    #[ink::test]
+    fn test_reward_rate_timelock() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 20, 86400);
+        assert_eq!(staking.max_reward_rate(), 20);
+        assert_eq!(staking.set_reward_rate(21), Err(StakingError::RateAboveMax));
+        assert_eq!(staking.set_period_rate(12, 21), Err(StakingError::RateAboveMax));
+        assert_eq!(staking.apply_pending_rate(), Err(StakingError::NoPendingRate));
 
-        assert_eq!(stake_info.amount, 10);
-        assert_eq!(stake_info.period, 6);
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        staking.set_reward_rate(10).unwrap();
+        let Event::RateChangeScheduled(scheduled) = last_event() else { panic!("expected RateChangeScheduled event") };
+        assert_eq!((scheduled.change, scheduled.effective_at), (RateChange::RewardRate(10), 1000 + 86400));
+        assert_eq!(staking.pending_reward_rate(), Some((10, 1000 + 86400)));
+        assert_eq!(staking.reward_rate(), 5);
+
+        test::set_block_timestamp::<DefaultEnvironment>(1000 + 86400 - 1);
+        assert_eq!(staking.apply_pending_rate(), Err(StakingError::TooEarly));
+        assert_eq!(staking.reward_rate(), 5);
+
+        test::set_block_timestamp::<DefaultEnvironment>(1000 + 86400);
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.apply_pending_rate().unwrap();
+        assert_eq!(staking.reward_rate(), 10);
+        assert_eq!(staking.pending_reward_rate(), None);
+        let Event::RewardRateChanged(changed) = last_event() else { panic!("expected RewardRateChanged event") };
+        assert_eq!((changed.old, changed.new), (5, 10));
     }
 
     #[ink::test]
-    fn test_emergency_withdraw() {
+    fn test_max_reward_rate_below_default() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
+        assert_eq!(
+            Staking::try_new(accounts.alice, accounts.alice, 1, 12, 12, 4, 0).err(),
+            Some(StakingError::RateAboveMax)
+        );
+    }
+
+    #[ink::test]
+    fn test_reward_token_onchain_balance() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let staking = Staking::new(accounts.alice, accounts.bob, 1, 12, 12, 100, 0);
+        assert_eq!(staking.reward_token_onchain_balance(), 0);
+
+        let contract = test::callee::<DefaultEnvironment>();
+        mock::set_balance(accounts.bob, contract, 4200);
+        // Other holders and other tokens are ignored
+        mock::set_balance(accounts.bob, accounts.charlie, 7);
+        mock::set_balance(accounts.alice, contract, 9);
+        assert_eq!(staking.reward_token_onchain_balance(), 4200);
+    }
+
+    #[ink::test]
+    fn test_stake_days() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        staking.set_allowed_days(vec![30, 90]).unwrap();
+        assert_eq!(staking.allowed_days(), vec![30, 90]);
 
-        // Set up initial stake
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        test::set_value_transferred::<DefaultEnvironment>(10);
-        staking.stake(6).unwrap();
+        assert_eq!(staking.set_allowed_days(Vec::new()), Err(StakingError::NotOwner));
+        assert_eq!(staking.stake_days(45, 36000), Err(StakingError::PeriodNotFound));
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        assert_eq!(staking.stake_days(90, 36000), Ok(1));
+        assert_eq!(staking.stake_days(30, 36000), Ok(2));
+        assert!(staking.stakes.get((accounts.bob, 0)).is_none());
 
-        // Ensure some time passes
-        test::advance_block::<DefaultEnvironment>();
+        let info = staking.stakes.get((accounts.bob, 1)).unwrap();
+        assert_eq!(info.active_until, 1000 + 90 * 86400);
+        assert_eq!(info.period, 0);
+        assert_eq!(info.amount, 36000);
+        assert_eq!(staking.pool_runway_days(), 1000);
+
+        // A month stake opens position 0 rather than topping up a day lock
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.get_staking_period(accounts.bob), Ok(6));
+        assert_eq!(staking.position_count(accounts.bob), 3);
+
+        // Rewards stop at the day lock like at a month lock
+        test::set_block_timestamp::<DefaultEnvironment>(1000 + 200 * 86400);
+        staking.withdraw_position(1).unwrap();
+        assert_eq!(staking.total_rewards_distributed(), 90 * 500);
+    }
+
+    #[ink::test]
+    fn test_set_allowed_days_validates() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_allowed_days(vec![30, 0]), Err(StakingError::InvalidDays));
+        assert_eq!(staking.set_allowed_days(vec![30, 90, 30]), Err(StakingError::InvalidDays));
+
+        staking.set_allowed_days(vec![30, 90]).unwrap();
+        let Event::ParamChanged(changed) = last_event() else { panic!("expected ParamChanged event") };
+        assert_eq!(changed.key, Param::AllowedDays as u8);
+        assert_eq!(changed.new, 2);
+        staking.set_allowed_days(Vec::new()).unwrap();
+        assert_eq!(staking.allowed_days(), Vec::<u32>::new());
+    }
+
+    #[ink::test]
+    fn test_auto_compound() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        staking.set_auto_compound(true).unwrap();
+        assert!(staking.auto_compound(accounts.bob));
+
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        let pending = staking.available_rewards(accounts.bob).unwrap();
+        assert_eq!(pending, 1000);
+        let events_before = recorded_events().len();
+        staking.stake(6, 500, false).unwrap();
+
+        assert_eq!(staking.staked_of(accounts.bob), 36000 + pending + 500);
+        assert_eq!(staking.total_staked(), 36000 + pending + 500);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        let events = recorded_events();
+        let new_events = &events[events_before..];
+        assert!(matches!(&new_events[0], Event::Compounded(compounded) if compounded.amount == pending));
+        assert!(!new_events.iter().any(|event| matches!(event, Event::Claim(_))));
+    }
+
+    #[ink::test]
+    fn test_auto_compound_needs_staked_reward_token() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.bob, 1, 12, 12, 100, 0);
+        assert_eq!(staking.set_auto_compound(true), Err(StakingError::RewardTokenMismatch));
+        staking.set_auto_compound(false).unwrap();
+    }
+
+    #[ink::test]
+    fn test_has_stake() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        assert!(!staking.has_stake(accounts.bob));
 
-        // Perform emergency withdraw
         test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        assert!(staking.has_stake(accounts.bob));
+
         staking.emergency_withdraw().unwrap();
+        assert!(staking.stakes.get((accounts.bob, 0)).is_some());
+        assert!(!staking.has_stake(accounts.bob));
+    }
 
-        let stake_info = staking.stakes.get(&accounts.bob).unwrap();
-        assert_eq!(stake_info.amount, 0);
+    #[ink::test]
+    fn test_auto_compound_withdraw_partial() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 36000, false).unwrap();
+        staking.set_auto_compound(true).unwrap();
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(stake_info.active_until);
+        let pending = staking.available_rewards(accounts.bob).unwrap();
+        assert_eq!(pending, 90000);
+
+        staking.withdraw_partial(1000).unwrap();
+        assert_eq!(staking.staked_of(accounts.bob), 36000 + pending - 1000);
+        assert_eq!(staking.total_staked(), 36000 + pending - 1000);
     }
 
+    #[ink::test]
+    fn test_stakers_fill_freed_index() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        for account in [accounts.bob, accounts.charlie, accounts.django] {
+            test::set_caller::<DefaultEnvironment>(account);
+            staking.stake(6, 100, false).unwrap();
+        }
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.emergency_withdraw().unwrap();
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob, accounts.django]);
+        assert_eq!(staking.staker_index.get(accounts.django), Some(1));
+        assert_eq!(staking.stakers.get(2), None);
+
+        staking.stake(6, 100, false).unwrap();
+        assert_eq!(staking.stakers_paged(0, 10), vec![accounts.bob, accounts.django, accounts.charlie]);
+        assert_eq!(staking.staker_count(), 3);
+    }
 
     #[ink::test]
-    fn test_extend() {
+    fn test_rate_changes_wait_for_delay() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 20, 7 * 86400);
+        fund_rewards(&mut staking, 1_000_000);
+
+        // 5 * 500 / 100 would pay 25 per period against the ceiling of 20
+        assert_eq!(staking.set_period_multiplier(6, 1_000_000), Err(StakingError::RateAboveMax));
+        assert_eq!(staking.set_period_multiplier(6, 500), Err(StakingError::RateAboveMax));
+        assert_eq!(staking.set_booster(Some(accounts.django), 50000), Err(StakingError::RateAboveMax));
+        assert_eq!(staking.set_rate_schedule(vec![(86400, 21)]), Err(StakingError::RateAboveMax));
 
-        // Set up initial stake
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        test::set_value_transferred::<DefaultEnvironment>(10);
-        staking.stake(6).unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        staking.set_period_multiplier(6, 300).unwrap();
+        staking.set_period_multiplier(6, 0).unwrap();
+        staking.set_period_rate(12, 8).unwrap();
+        assert_eq!(
+            staking.pending_rate_changes(),
+            vec![(RateChange::PeriodMultiplier(6, 0), 7 * 86400), (RateChange::PeriodRate(12, 8), 7 * 86400)]
+        );
 
-        // Ensure some time passes
-        test::advance_block::<DefaultEnvironment>();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(500));
+        assert_eq!(staking.apply_pending_rate(), Err(StakingError::TooEarly));
+
+        test::set_block_timestamp::<DefaultEnvironment>(7 * 86400);
+        staking.apply_pending_rate().unwrap();
+        assert!(staking.pending_rate_changes().is_empty());
+        assert_eq!(staking.period_multipliers.get(6), Some(0));
+        assert_eq!(staking.period_reward_rate.get(12), Some(8));
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_top_up_expired_lock_starts_over() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 100_000_000);
 
-        // Perform extend
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        staking.extend(6).unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        let old = staking.stakes.get((accounts.bob, 0)).unwrap();
+        let now = old.active_until + 10 * 86400;
+        test::set_block_timestamp::<DefaultEnvironment>(now);
+        staking.stake(12, 1_000_000, false).unwrap();
 
-        let stake_info = staking.stakes.get(&accounts.bob).unwrap();
-        assert_eq!(stake_info.period, 6);
+        let stake_info = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(stake_info.period, 12);
+        assert_eq!(stake_info.started_at, now);
+        assert_eq!(stake_info.active_until, now + 12 * 30 * 86400);
+        assert_eq!(staking.is_locked(accounts.bob), Ok(true));
+        // The ten days after the old end aren't paid, the next hundred are
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(0));
+        test::set_block_timestamp::<DefaultEnvironment>(now + 100 * 86400);
+        assert_eq!(staking.available_rewards(accounts.bob), Ok(100 * 1_036_000 * 5 * 100 / 36000));
     }
 
     #[ink::test]
-    fn test_withdraw() {
-        init();
+    fn test_reward_pauses_skipped_lazily() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
-        let amount = 10;
-        // Set up initial stake
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        test::set_value_transferred::<DefaultEnvironment>(amount);
-        staking.stake(6).unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        test::set_caller::<DefaultEnvironment>(accounts.alice);
+        for (pause, resume) in [(86400, 3 * 86400), (6 * 86400, 8 * 86400)] {
+            test::set_block_timestamp::<DefaultEnvironment>(pause);
+            staking.pause_rewards().unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(resume);
+            staking.resume_rewards().unwrap();
+        }
+        assert_eq!(staking.reward_pause_count, 2);
+        assert_eq!(staking.last_claim_at(accounts.bob), 0);
 
-        // Ensure some time passes
-        test::advance_block::<DefaultEnvironment>();
-        // Query the native balance of Bob's account
-        let bob_native_balance_before = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+        // Ten days with four paused
+        test::set_block_timestamp::<DefaultEnvironment>(10 * 86400);
+        assert_eq!(staking.passed_reward_periods(accounts.bob), Ok(6));
+        assert_eq!(staking.next_reward_date(accounts.bob), Ok(11 * 86400));
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.claim().unwrap();
+        assert_eq!(staking.last_claim_at(accounts.bob), 10 * 86400);
+
+        // A clock inside the first window only counts from its end
+        test::set_caller::<DefaultEnvironment>(accounts.charlie);
+        staking.stake(6, 36000, false).unwrap();
+        staking.last_reward_claims.insert((accounts.charlie, 0), &(2 * 86400));
+        assert_eq!(staking.passed_reward_periods(accounts.charlie), Ok(5));
+        assert_eq!(staking.next_reward_date(accounts.charlie), Ok(11 * 86400));
+        staking.claim().unwrap();
+        assert_eq!(staking.last_claim_at(accounts.charlie), 10 * 86400);
+    }
+
+    #[ink::test]
+    fn test_reward_token_amount_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        assert_eq!(
+            Staking::try_new(accounts.alice, accounts.bob, 1, 40, 0, 100, 0).err(),
+            Some(StakingError::InvalidDecimals)
+        );
+        let widest = Staking::try_new(accounts.alice, accounts.bob, 1, 38, 0, 100, 0).ok().unwrap();
+        assert_eq!(widest._reward_token_amount(3), Ok(3 * 10u128.pow(38)));
+        assert_eq!(widest._reward_token_amount(4), Err(StakingError::RewardOverflow));
+        let narrowest = Staking::try_new(accounts.alice, accounts.bob, 1, 0, 38, 100, 0).ok().unwrap();
+        assert_eq!(narrowest._reward_token_amount(u128::MAX), Ok(u128::MAX / 10u128.pow(38)));
+        let rate = Staking::new(accounts.alice, accounts.bob, u128::MAX, 12, 12, 100, 0);
+        assert_eq!(rate._reward_token_amount(2), Err(StakingError::RewardOverflow));
+    }
+
+    #[ink::test]
+    #[should_panic(expected = "decimals too far apart")]
+    fn test_new_panics_on_wide_decimals() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        Staking::new(accounts.alice, accounts.bob, 1, 0, 40, 100, 0);
+    }
+
+    #[ink::test]
+    fn test_position_variants_leave_position_0_alone() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
 
-        // Perform withdraw
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        staking.withdraw().unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        assert_eq!(staking.stake_new(6, 36000), Ok(1));
 
-        let bob_native_balance_after = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.compound_position(1).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 36500);
+        assert_eq!(staking.withdraw_partial_position(1, 500), Err(StakingError::StillLocked));
 
-        info!("BOB BEFORE BALANCE: {}, BOB AFTER BALANCE: {}", bob_native_balance_before, bob_native_balance_after);
+        let until = staking.stakes.get((accounts.bob, 1)).unwrap().active_until;
+        test::set_block_timestamp::<DefaultEnvironment>(until + 1);
+        staking.withdraw_partial_position(1, 500).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 36000);
+        assert_eq!(staking.extend_position(1, 6), Ok(until + 1 + 6 * 30 * 86400));
+        staking.emergency_withdraw_position(1).unwrap();
+        assert_eq!(staking.stakes.get((accounts.bob, 1)).unwrap().amount, 0);
 
-        let stake_info = staking.stakes.get(&accounts.bob).unwrap();
-        assert_eq!(stake_info.amount, 0);
-        assert_eq!(bob_native_balance_before, bob_native_balance_after - amount)
+        let position_0 = staking.stakes.get((accounts.bob, 0)).unwrap();
+        assert_eq!(position_0.amount, 36000);
+        assert_eq!(position_0.active_until, 6 * 30 * 86400);
     }
 
     #[ink::test]
-    fn test_claim() {
-        init();
+    fn test_compound_respects_cap_and_records_history() {
         let accounts = test::default_accounts::<DefaultEnvironment>();
-        let mut staking = Staking::new(accounts.alice, 1);
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        staking.set_max_total_staked(36500).unwrap();
 
-        // Set up initial stake
         test::set_caller::<DefaultEnvironment>(accounts.bob);
-        test::set_value_transferred::<DefaultEnvironment>(10);
-        staking.stake(6).unwrap();
+        staking.stake(6, 36000, false).unwrap();
+        test::set_block_timestamp::<DefaultEnvironment>(86400);
+        staking.compound().unwrap();
+        assert_eq!(staking.total_staked(), 36500);
+        assert_eq!(staking.history_of(accounts.bob)[1..], [
+            StakeEvent::Claimed { timestamp: 86400, amount: 500 },
+            StakeEvent::Staked { timestamp: 86400, amount: 500 },
+        ]);
 
-        // Ensure some time passes
-        test::advance_block::<DefaultEnvironment>();
+        test::set_block_timestamp::<DefaultEnvironment>(2 * 86400);
+        assert_eq!(staking.compound(), Err(StakingError::CapExceeded));
 
-        // Perform claim
+        // Auto-compound pays out instead of breaking the cap
+        staking.set_auto_compound(true).unwrap();
+        let until = staking.stakes.get((accounts.bob, 0)).unwrap().active_until;
+        test::set_block_timestamp::<DefaultEnvironment>(until);
+        staking.withdraw_partial(500).unwrap();
+        assert_eq!(staking.total_staked(), 36000);
+        assert!(recorded_events().iter().any(|event| matches!(event, Event::Claim(claim) if claim.account == accounts.bob)));
+    }
+
+    #[ink::test]
+    fn test_staker_count_follows_open_positions() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        fund_rewards(&mut staking, 1_000_000);
+        let info = |amount| StakeInfo { amount, started_at: 0, period: 6, active_until: 6 * 30 * 86400 };
+
+        // Migrating a position in and back out to zero lists and drops the account
+        staking.migrate_stake(accounts.bob, info(1000), 0).unwrap();
+        assert_eq!(staking.staker_count(), 1);
+        staking.migrate_stake(accounts.bob, info(0), 0).unwrap();
+        assert_eq!(staking.staker_count(), 0);
+        assert!(!staking.has_stake(accounts.bob));
+
+        // Only the last open position of an account takes it off the list
         test::set_caller::<DefaultEnvironment>(accounts.bob);
+        staking.stake(6, 1000, false).unwrap();
+        staking.stake_new(6, 1000).unwrap();
+        assert_eq!(staking.staker_count(), 1);
+        test::set_block_timestamp::<DefaultEnvironment>(6 * 30 * 86400);
+        staking.withdraw_partial(400).unwrap();
+        staking.withdraw_position(1).unwrap();
+        assert_eq!(staking.staker_count(), 1);
+        staking.withdraw_partial(600).unwrap();
+        assert_eq!(staking.staker_count(), 0);
+        assert_eq!(staking.stakers_paged(0, 10), Vec::new());
+    }
 
-        let stake_info = staking.stakes.get(&accounts.bob).unwrap();
-        info!("ACTIVE UNTIL {}", stake_info.active_until);
+    #[ink::test]
+    fn test_conflicting_rate_changes() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 1000);
 
-        // staking.claim().unwrap();
+        // Each fits under the ceiling of 100 alone, together 60 * 200% would not
+        staking.set_reward_rate(60).unwrap();
+        staking.set_period_multiplier(12, 200).unwrap();
+        assert_eq!(staking.pending_rate_changes().len(), 2);
 
-        // let reward = staking.available_rewards(accounts.bob).unwrap();
-        // assert!(reward > 0);
+        test::set_block_timestamp::<DefaultEnvironment>(1000);
+        staking.apply_pending_rate().unwrap();
+        let Event::RateChangeDropped(dropped) = last_event() else { panic!("expected RateChangeDropped event") };
+        assert_eq!(dropped.change, RateChange::PeriodMultiplier(12, 200));
+        assert_eq!(staking.reward_rate(), 60);
+        assert_eq!(staking.period_multipliers.get(12), None);
+        assert!(staking.pending_rate_changes().is_empty());
+    }
+
+    #[ink::test]
+    fn test_slash_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.add_operator(accounts.alice).unwrap();
+
+        staking.stakes.insert((accounts.bob, 0), &StakeInfo {
+            amount: u128::MAX / 2,
+            started_at: 0,
+            period: 6,
+            active_until: 0,
+        });
+        assert_eq!(staking.slash(accounts.bob, 5000, 0), Err(StakingError::Overflow));
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, u128::MAX / 2);
+    }
+
+    #[ink::test]
+    fn test_early_withdraw_fee_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+
+        test::set_block_timestamp::<DefaultEnvironment>(1_000);
+        staking.stakes.insert((accounts.bob, 0), &StakeInfo {
+            amount: u128::MAX / 2,
+            started_at: 1_000,
+            period: 6,
+            active_until: 1_000 + 6 * 30 * 86_400_000,
+        });
+        assert_eq!(staking.early_withdraw_preview(accounts.bob), Err(StakingError::Overflow));
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.emergency_withdraw(), Err(StakingError::Overflow));
+        assert_eq!(staking.stakes.get((accounts.bob, 0)).unwrap().amount, u128::MAX / 2);
+    }
+
+    #[ink::test]
+    fn test_referral_bonus_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_referral_bonus(1000).unwrap();
+        staking.referrers.insert(accounts.bob, &accounts.charlie);
+
+        assert_eq!(staking._referral_bonus(accounts.bob, u128::MAX / 1000), Ok(u128::MAX / 1000 / 10));
+        assert_eq!(staking._referral_bonus(accounts.bob, u128::MAX / 100), Err(StakingError::RewardOverflow));
+        assert_eq!(staking._referral_bonus(accounts.charlie, u128::MAX), Ok(0));
+    }
+
+    #[ink::test]
+    fn test_stake_days_overflow() {
+        let accounts = test::default_accounts::<DefaultEnvironment>();
+        let mut staking = Staking::new(accounts.alice, accounts.alice, 1, 12, 12, 100, 0);
+        staking.set_allowed_days(vec![2]).unwrap();
+        staking.reward_period_secs = u64::MAX;
+
+        test::set_caller::<DefaultEnvironment>(accounts.bob);
+        assert_eq!(staking.stake_days(2, 100), Err(StakingError::Overflow));
+        assert_eq!(staking.position_count(accounts.bob), 0);
+        assert_eq!(staking.total_staked(), 0);
     }
+}
+
+/// Runs against a local `substrate-contracts-node`, see `cargo test --features e2e-tests`.
+/// Block timestamps are milliseconds there, so a one-unit reward period lets a
+/// one-period day lock expire by the next block.
+#[cfg(all(test, feature = "e2e-tests"))]
+mod e2e_tests {
+    use crate::staking::{StakeInfo, StakingError, StakingRef};
+    use ink_e2e::build_message;
+    use openbrush::contracts::traits::psp22::psp22_external::PSP22;
+    use token::usdt_psp22::USDTRef;
+
+    type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    #[ink_e2e::test]
+    async fn e2e_stake_claim_withdraw_move_tokens(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+        let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+        let staking_token = client
+            .instantiate("token", &ink_e2e::alice(), USDTRef::new(1_000_000), 0, None)
+            .await
+            .expect("staking token instantiate failed")
+            .account_id;
+        let reward_token = client
+            .instantiate("token", &ink_e2e::alice(), USDTRef::new(1_000_000), 0, None)
+            .await
+            .expect("reward token instantiate failed")
+            .account_id;
+        let staking = client
+            .instantiate("staking", &ink_e2e::alice(), StakingRef::new(staking_token, reward_token, 1, 12, 12, 100, 0), 0, None)
+            .await
+            .expect("staking instantiate failed")
+            .account_id;
+
+        // One-period day locks and a reward budget backed by reward tokens
+        let set_period = build_message::<StakingRef>(staking).call(|s| s.set_reward_period(1));
+        client.call(&ink_e2e::alice(), set_period, 0, None).await.expect("set_reward_period failed");
+        let set_days = build_message::<StakingRef>(staking).call(|s| s.set_allowed_days(vec![1]));
+        client.call(&ink_e2e::alice(), set_days, 0, None).await.expect("set_allowed_days failed");
+        let approve = build_message::<USDTRef>(reward_token).call(|t| t.approve(staking, 500_000));
+        client.call(&ink_e2e::alice(), approve, 0, None).await.expect("approve failed");
+        let fund = build_message::<StakingRef>(staking).call(|s| s.fund_rewards_psp22(500_000));
+        client.call(&ink_e2e::alice(), fund, 0, None).await.expect("fund_rewards_psp22 failed");
+        let pool = build_message::<StakingRef>(staking).call(|s| s.update_rewards_pool());
+        client.call(&ink_e2e::alice(), pool, 500_000, None).await.expect("update_rewards_pool failed");
+
+        // Bob stakes 36000 of his 50000, a period pays him 500
+        let send = build_message::<USDTRef>(staking_token).call(|t| t.transfer(bob, 50_000, Vec::new()));
+        client.call(&ink_e2e::alice(), send, 0, None).await.expect("transfer failed");
+        let approve = build_message::<USDTRef>(staking_token).call(|t| t.approve(staking, 36_000));
+        client.call(&ink_e2e::bob(), approve, 0, None).await.expect("approve failed");
+        let stake = build_message::<StakingRef>(staking).call(|s| s.stake_days(1, 36_000));
+        let position = client
+            .call(&ink_e2e::bob(), stake, 0, None)
+            .await
+            .expect("stake_days failed")
+            .return_value()
+            .expect("stake_days returned an error");
+        assert_eq!(balance_of(&mut client, staking_token, bob).await, 14_000);
+        assert_eq!(balance_of(&mut client, staking_token, staking).await, 36_000);
+
+        // The claim pays reward tokens out of the contract's own balance
+        let claim = build_message::<StakingRef>(staking).call(|s| s.claim_position(position));
+        client
+            .call(&ink_e2e::bob(), claim, 0, None)
+            .await
+            .expect("claim_position failed")
+            .return_value()
+            .expect("claim_position returned an error");
+        assert_eq!(balance_of(&mut client, reward_token, bob).await, 500);
+        assert_eq!(balance_of(&mut client, reward_token, staking).await, 499_500);
+
+        // The withdrawal returns exactly the principal
+        let withdraw = build_message::<StakingRef>(staking).call(|s| s.withdraw_position(position));
+        client
+            .call(&ink_e2e::bob(), withdraw, 0, None)
+            .await
+            .expect("withdraw_position failed")
+            .return_value()
+            .expect("withdraw_position returned an error");
+        assert_eq!(balance_of(&mut client, staking_token, bob).await, 50_000);
+        assert_eq!(balance_of(&mut client, staking_token, staking).await, 0);
+        let total = build_message::<StakingRef>(staking).call(|s| s.total_staked());
+        assert_eq!(client.call_dry_run(&ink_e2e::alice(), &total, 0, None).await.return_value(), 0);
+        Ok(())
+    }
+
+    #[ink_e2e::test]
+    async fn e2e_failed_withdraw_keeps_the_position(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+        let bob = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+        let staking_token = client
+            .instantiate("token", &ink_e2e::alice(), USDTRef::new(1_000_000), 0, None)
+            .await
+            .expect("staking token instantiate failed")
+            .account_id;
+        let staking = client
+            .instantiate("staking", &ink_e2e::alice(), StakingRef::new(staking_token, staking_token, 1, 12, 12, 100, 0), 0, None)
+            .await
+            .expect("staking instantiate failed")
+            .account_id;
+
+        // A migrated position the contract holds no tokens for, already unlocked
+        let info = StakeInfo { amount: 1000, started_at: 0, period: 6, active_until: 1 };
+        let migrate = build_message::<StakingRef>(staking).call(|s| s.migrate_stake(bob, info.clone(), 1));
+        client.call(&ink_e2e::alice(), migrate, 0, None).await.expect("migrate_stake failed");
+
+        // The transfer out fails after `_withdraw` zeroed the position, the revert restores it
+        let withdraw = build_message::<StakingRef>(staking).call(|s| s.withdraw());
+        let dry_run = client.call_dry_run(&ink_e2e::bob(), &withdraw, 0, None).await;
+        assert!(dry_run.exec_return_value().did_revert());
+        assert_eq!(dry_run.return_value(), Err(StakingError::TransferFailed));
+        // Submitted for real, the extrinsic fails with `ContractReverted`
+        let submitted = client.call(&ink_e2e::bob(), withdraw, 0, None).await;
+        assert!(matches!(submitted, Err(ink_e2e::Error::CallExtrinsic(_))));
 
-}
\ No newline at end of file
+        let staked_of = build_message::<StakingRef>(staking).call(|s| s.staked_of(bob));
+        assert_eq!(client.call_dry_run(&ink_e2e::alice(), &staked_of, 0, None).await.return_value(), 1000);
+        let total = build_message::<StakingRef>(staking).call(|s| s.total_staked());
+        assert_eq!(client.call_dry_run(&ink_e2e::alice(), &total, 0, None).await.return_value(), 1000);
+        let view = build_message::<StakingRef>(staking).call(|s| s.all_stake_info(bob));
+        let view = client.call_dry_run(&ink_e2e::alice(), &view, 0, None).await.return_value().expect("position is gone");
+        assert_eq!((view.amount, view.period, view.active_until), (1000, 6, 1));
+        Ok(())
+    }
+
+    async fn balance_of(
+        client: &mut ink_e2e::Client<ink_e2e::PolkadotConfig, ink::env::DefaultEnvironment>,
+        token: ink::primitives::AccountId,
+        owner: ink::primitives::AccountId,
+    ) -> u128 {
+        let query = build_message::<USDTRef>(token).call(|t| t.balance_of(owner));
+        client.call_dry_run(&ink_e2e::alice(), &query, 0, None).await.return_value()
+    }
+}